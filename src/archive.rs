@@ -0,0 +1,315 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use tar::{Archive, EntryType};
+use tempfile::TempPath;
+use xz2::read::XzDecoder;
+use zip::ZipArchive;
+
+use crate::utils::{persist_tmp_path, write_tmp_file_for};
+
+// Release file formats: binary packed in an archive, or the binary itself
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum ArchiveType {
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "tar.xz")]
+    TarXz,
+    #[serde(rename = "binary")]
+    Binary,
+}
+
+// Detects archive type from the file's magic bytes, anything that is not a
+// known archive is considered a binary
+pub fn detect_archive_type(mut file: &File) -> Result<ArchiveType, Box<dyn Error>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut magic = Vec::with_capacity(6);
+    file.take(6).read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+    let archive_type = if magic.starts_with(ZIP_MAGIC) {
+        ArchiveType::Zip
+    } else if magic.starts_with(GZIP_MAGIC) {
+        ArchiveType::TarGz
+    } else if magic.starts_with(XZ_MAGIC) {
+        ArchiveType::TarXz
+    } else {
+        ArchiveType::Binary
+    };
+    Ok(archive_type)
+}
+
+// Extracts binary from downloaded file into given path. For archives, only
+// the regular file entry at member path is extracted; entries with absolute
+// or parent directory paths never match. Archive type is detected unless
+// given.
+pub fn extract_binary(
+    file: File,
+    archive_type: Option<ArchiveType>,
+    member: &str,
+    dest: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let tmp_path = extract_binary_to_tmp_file(file, archive_type, member, dest)?;
+    persist_tmp_path(tmp_path, dest)
+}
+
+// Same as extract_binary, but into a temporary file next to given path, e.g.
+// to check the binary before it is persisted into place
+pub fn extract_binary_to_tmp_file(
+    file: File,
+    archive_type: Option<ArchiveType>,
+    member: &str,
+    dest: &Path,
+) -> Result<TempPath, Box<dyn Error>> {
+    extract_binary_with_limit(file, archive_type, member, dest, MAX_BINARY_SIZE)
+}
+
+fn extract_binary_with_limit(
+    mut file: File,
+    archive_type: Option<ArchiveType>,
+    member: &str,
+    dest: &Path,
+    limit: u64,
+) -> Result<TempPath, Box<dyn Error>> {
+    let archive_type = match archive_type {
+        Some(archive_type) => archive_type,
+        None => detect_archive_type(&file)?,
+    };
+    let member_path = normalize_member_path(Path::new(member))
+        .ok_or_else(|| format!("Invalid archive member path '{}'", member))?;
+    match archive_type {
+        ArchiveType::Zip => {
+            let mut zip_archive = ZipArchive::new(file)?;
+            for i in 0..zip_archive.len() {
+                let zip_file = zip_archive.by_index(i)?;
+                if !zip_file.is_file()
+                    || zip_file.enclosed_name().and_then(normalize_member_path)
+                        != Some(member_path.clone())
+                {
+                    continue;
+                }
+                check_size(zip_file.size(), limit, member)?;
+                let mut reader = LimitedReader::new(zip_file, limit, member);
+                return write_tmp_file_for(dest, &mut reader, true);
+            }
+        }
+        ArchiveType::TarGz => {
+            file.seek(SeekFrom::Start(0))?;
+            return extract_tar_member(GzDecoder::new(file), &member_path, member, dest, limit);
+        }
+        ArchiveType::TarXz => {
+            file.seek(SeekFrom::Start(0))?;
+            return extract_tar_member(XzDecoder::new(file), &member_path, member, dest, limit);
+        }
+        ArchiveType::Binary => {
+            check_size(file.metadata()?.len(), limit, member)?;
+            file.seek(SeekFrom::Start(0))?;
+            let mut reader = LimitedReader::new(file, limit, member);
+            return write_tmp_file_for(dest, &mut reader, true);
+        }
+    }
+    Err(format!("File '{}' not found in archive", member).into())
+}
+
+fn extract_tar_member<R: Read>(
+    reader: R,
+    member_path: &Path,
+    member: &str,
+    dest: &Path,
+    limit: u64,
+) -> Result<TempPath, Box<dyn Error>> {
+    let mut archive = Archive::new(reader);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if entry.header().entry_type() != EntryType::Regular
+            || normalize_member_path(&entry.path()?).as_deref() != Some(member_path)
+        {
+            continue;
+        }
+        check_size(entry.size(), limit, member)?;
+        let mut reader = LimitedReader::new(entry, limit, member);
+        return write_tmp_file_for(dest, &mut reader, true);
+    }
+    Err(format!("File '{}' not found in archive", member).into())
+}
+
+// Relative path without '.' components, or None for absolute paths and
+// paths that contain '..'
+pub fn normalize_member_path(path: &Path) -> Option<PathBuf> {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => result.push(c),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    if result.as_os_str().is_empty() {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+fn check_size(size: u64, limit: u64, member: &str) -> Result<(), Box<dyn Error>> {
+    if size > limit {
+        return Err(size_limit_error(member, limit).into());
+    }
+    Ok(())
+}
+
+fn size_limit_error(member: &str, limit: u64) -> String {
+    format!("File '{}' exceeds size limit of {} bytes", member, limit)
+}
+
+// Declared sizes in archive headers cannot be trusted, so the actual amount of
+// data read is limited too
+struct LimitedReader<'a, R> {
+    inner: R,
+    remaining: u64,
+    limit: u64,
+    member: &'a str,
+}
+
+impl<'a, R: Read> LimitedReader<'a, R> {
+    fn new(inner: R, limit: u64, member: &'a str) -> Self {
+        LimitedReader {
+            inner,
+            remaining: limit,
+            limit,
+            member,
+        }
+    }
+}
+
+impl<R: Read> Read for LimitedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n as u64 > self.remaining {
+            return Err(io::Error::other(size_limit_error(self.member, self.limit)));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+
+const XZ_MAGIC: &[u8] = b"\xfd7zXZ\x00";
+
+const MAX_BINARY_SIZE: u64 = 1024 * 1024 * 1024;
+
+#[cfg(test)]
+mod tests {
+
+    use std::{
+        fs::read,
+        io::{Cursor, Write},
+    };
+
+    use flate2::{write::GzEncoder, Compression};
+    use tar::{Builder, Header};
+    use tempfile::{tempdir, tempfile};
+    use xz2::write::XzEncoder;
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    fn tar_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        for (path, content) in entries {
+            let mut header = Header::new_gnu();
+            // Set name bytes directly, as set_path refuses '..' paths
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            header.set_entry_type(EntryType::Regular);
+            header.set_cksum();
+            builder.append(&header, *content).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn to_file(bytes: &[u8]) -> File {
+        let mut file = tempfile().unwrap();
+        file.write_all(bytes).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_extract_binary_tar_gz() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&tar_bytes(&[
+                ("../terraform-docs", b"evil"),
+                ("README.md", b"readme"),
+                ("./terraform-docs", b"binary"),
+            ]))
+            .unwrap();
+        let file = to_file(&encoder.finish().unwrap());
+        assert_eq!(detect_archive_type(&file).unwrap(), ArchiveType::TarGz);
+        let dir = tempdir().unwrap();
+        let dest = dir.path().join("0.16.0");
+        extract_binary(file, None, "terraform-docs", &dest).unwrap();
+        assert_eq!(read(&dest).unwrap(), b"binary");
+    }
+
+    #[test]
+    fn test_extract_binary_tar_xz() {
+        let mut encoder = XzEncoder::new(Vec::new(), 6);
+        encoder
+            .write_all(&tar_bytes(&[("bin/tfsec", b"binary")]))
+            .unwrap();
+        let file = to_file(&encoder.finish().unwrap());
+        assert_eq!(detect_archive_type(&file).unwrap(), ArchiveType::TarXz);
+        let dir = tempdir().unwrap();
+        let dest = dir.path().join("1.28.1");
+        assert!(extract_binary(file.try_clone().unwrap(), None, "tfsec", &dest).is_err());
+        extract_binary(file, None, "bin/tfsec", &dest).unwrap();
+        assert_eq!(read(&dest).unwrap(), b"binary");
+    }
+
+    #[test]
+    fn test_extract_binary_zip() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("terraform", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"binary").unwrap();
+        let file = to_file(&writer.finish().unwrap().into_inner());
+        assert_eq!(detect_archive_type(&file).unwrap(), ArchiveType::Zip);
+        let dir = tempdir().unwrap();
+        let dest = dir.path().join("1.5.7");
+        assert!(extract_binary(file.try_clone().unwrap(), None, "../terraform", &dest).is_err());
+        extract_binary(file, None, "terraform", &dest).unwrap();
+        assert_eq!(read(&dest).unwrap(), b"binary");
+    }
+
+    #[test]
+    fn test_extract_binary_raw() {
+        let file = to_file(b"\x7fELF binary");
+        assert_eq!(detect_archive_type(&file).unwrap(), ArchiveType::Binary);
+        let dir = tempdir().unwrap();
+        let dest = dir.path().join("0.38.4");
+        assert!(extract_binary_with_limit(file.try_clone().unwrap(), None, "x", &dest, 4).is_err());
+        assert!(!dest.exists());
+        extract_binary(file, None, "terragrunt", &dest).unwrap();
+        assert_eq!(read(&dest).unwrap(), b"\x7fELF binary");
+    }
+
+    #[test]
+    fn test_limited_reader() {
+        let mut reader = LimitedReader::new(&b"123456"[..], 4, "x");
+        let mut content = Vec::new();
+        assert!(reader.read_to_end(&mut content).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    error::Error,
+    fs::{create_dir_all, read_to_string, File},
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use tar::{Archive, Builder, EntryType};
+
+use crate::{
+    archive::normalize_member_path,
+    shared::{install_binary_version, Binary, DotDir, InstallOptions},
+    utils::{write_file_atomically, NEWLINE},
+};
+
+// A bundle is a tar file for installing binaries without network access. It
+// holds the original release files (archive, SHA256SUMS and signature) in the
+// download cache layout, and a manifest of binaries, versions and platform.
+// Importing installs from the bundle only, so release files are verified
+// exactly like when downloaded. Without a PGP signature (terragrunt, tools,
+// opentofu without a key), a bundle is only checked for consistency with its
+// own SHA256SUMS.
+#[derive(Deserialize, Serialize)]
+struct Manifest {
+    os: String,
+    arch: String,
+    binaries: Vec<ManifestBinary>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ManifestBinary {
+    name: String,
+    versions: Vec<String>,
+}
+
+pub fn export_bundle(
+    binaries: Vec<(Binary, Vec<String>)>,
+    dot_dir: DotDir,
+    os: String,
+    arch: String,
+    bundle_path: &Path,
+) -> Result<String, Box<dyn Error>> {
+    if binaries.is_empty() {
+        return Err("Nothing to export, give versions with e.g. --tf 1.5.7".into());
+    }
+    // Install into a scratch tree with an empty cache, so that the cache ends
+    // up holding exactly the verified release files. Keys are read from etc.
+    let tmp_home = tempfile::tempdir()?;
+    let mut tmp_dot_dir = DotDir::bootstrap(tmp_home.path())?;
+    tmp_dot_dir.etc = dot_dir.etc.clone();
+    let cache_dir = tmp_home.path().join(BUNDLE_CACHE_DIR_NAME);
+    let options = InstallOptions {
+        cache_dir: Some(cache_dir.clone()),
+        ..Default::default()
+    };
+    let mut manifest = Manifest {
+        os: os.clone(),
+        arch: arch.clone(),
+        binaries: Vec::new(),
+    };
+    for (binary, versions) in binaries {
+        for version in &versions {
+            create_dir_all(tmp_dot_dir.opt.join(&binary))?;
+            create_dir_all(tmp_dot_dir.var.join(&binary))?;
+            install_binary_version(
+                binary.clone(),
+                version.clone(),
+                tmp_dot_dir.clone(),
+                os.clone(),
+                arch.clone(),
+                &options,
+            )?;
+        }
+        manifest.binaries.push(ManifestBinary {
+            name: binary.to_string(),
+            versions,
+        });
+    }
+    let manifest_toml = toml::to_string(&manifest)?;
+    let mut tmp_bundle_file = tempfile::tempfile()?;
+    {
+        let mut builder = Builder::new(&tmp_bundle_file);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest_toml.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, MANIFEST_FILE_NAME, manifest_toml.as_bytes())?;
+        builder.append_dir_all(BUNDLE_CACHE_DIR_NAME, &cache_dir)?;
+        builder.finish()?;
+    }
+    tmp_bundle_file.seek(SeekFrom::Start(0))?;
+    write_file_atomically(bundle_path, &mut tmp_bundle_file, false)?;
+    Ok(format!(
+        "Exported {} ({}/{}) to {}",
+        describe(&manifest),
+        os,
+        arch,
+        bundle_path.display()
+    ))
+}
+
+pub fn import_bundle(
+    bundle_path: &Path,
+    dot_dir: DotDir,
+    os: String,
+    arch: String,
+) -> Result<String, Box<dyn Error>> {
+    let tmp_dir = tempfile::tempdir()?;
+    extract_bundle(&File::open(bundle_path)?, tmp_dir.path())?;
+    let manifest_path = tmp_dir.path().join(MANIFEST_FILE_NAME);
+    if !manifest_path.is_file() {
+        return Err(format!("Invalid bundle {}: no manifest", bundle_path.display()).into());
+    }
+    let manifest: Manifest = toml::from_str(&read_to_string(manifest_path)?)?;
+    if manifest.os != os || manifest.arch != arch {
+        return Err(format!(
+            "Bundle is for {}/{}, not for this platform ({}/{})",
+            manifest.os, manifest.arch, os, arch
+        )
+        .into());
+    }
+    let options = InstallOptions {
+        offline: true,
+        cache_dir: Some(tmp_dir.path().join(BUNDLE_CACHE_DIR_NAME)),
+        ..Default::default()
+    };
+    let mut result = Vec::new();
+    for manifest_binary in &manifest.binaries {
+        let binary = Binary::lookup(&manifest_binary.name, &dot_dir)?;
+        for version in &manifest_binary.versions {
+            result.push(install_binary_version(
+                binary.clone(),
+                version.clone(),
+                dot_dir.clone(),
+                os.clone(),
+                arch.clone(),
+                &options,
+            )?);
+        }
+    }
+    Ok(result.join(NEWLINE))
+}
+
+// Only the manifest and cache files are extracted, bundles with any other
+// entries (e.g. links, or absolute or parent directory paths) are rejected
+fn extract_bundle(bundle_file: &File, dest_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut archive = Archive::new(bundle_file);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        let path = normalize_member_path(&entry.path()?).ok_or("Invalid bundle: unsafe path")?;
+        if entry_type == EntryType::Directory {
+            continue;
+        }
+        let valid = entry_type == EntryType::Regular
+            && (path == Path::new(MANIFEST_FILE_NAME) || is_cache_file_path(&path));
+        if !valid {
+            return Err(format!("Invalid bundle: unexpected entry {}", path.display()).into());
+        }
+        let dest_path = dest_dir.join(&path);
+        create_dir_all(dest_path.parent().ok_or("Invalid file path")?)?;
+        let mut content = (&mut entry).take(MAX_BUNDLE_ENTRY_SIZE);
+        write_file_atomically(&dest_path, &mut content, false)?;
+        if entry.read(&mut [0])? != 0 {
+            return Err(format!("Invalid bundle: {} is too large", path.display()).into());
+        }
+    }
+    Ok(())
+}
+
+// cache/index/<sha256> or cache/blobs/<sha256>
+fn is_cache_file_path(path: &Path) -> bool {
+    let components: Vec<_> = path.iter().filter_map(|c| c.to_str()).collect();
+    matches!(
+        components.as_slice(),
+        [BUNDLE_CACHE_DIR_NAME, "index" | "blobs", name]
+            if name.len() == 64 && name.chars().all(|c| c.is_ascii_hexdigit())
+    )
+}
+
+fn describe(manifest: &Manifest) -> String {
+    manifest
+        .binaries
+        .iter()
+        .flat_map(|b| b.versions.iter().map(move |v| format!("{} {}", b.name, v)))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+const MANIFEST_FILE_NAME: &str = "bundle.toml";
+
+const BUNDLE_CACHE_DIR_NAME: &str = "cache";
+
+const MAX_BUNDLE_ENTRY_SIZE: u64 = 1024 * 1024 * 1024;
+
+#[cfg(test)]
+mod tests {
+
+    use std::{
+        fs::{read_dir, write},
+        io::Write,
+    };
+
+    use reqwest::Url;
+    use tempfile::tempdir;
+
+    use crate::{shared::get_os_arch, utils::sha256_hex};
+
+    use super::*;
+
+    // Exports terragrunt from a file:// mirror, then swaps the release file
+    // in the bundle (keeping the cache itself consistent)
+    #[cfg(unix)]
+    #[test]
+    fn test_import_rejects_tampered_bundle() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let (os, arch) = get_os_arch();
+        let file_name = format!("terragrunt_{}_{}", os, arch);
+        let content = "#!/bin/sh\necho terragrunt version v0.38.4\n";
+        let mirror_dir = tempdir().unwrap();
+        let release_dir = mirror_dir.path().join("v0.38.4");
+        create_dir_all(&release_dir).unwrap();
+        write(release_dir.join(&file_name), content).unwrap();
+        let sha256 = sha256_hex(&File::open(release_dir.join(&file_name)).unwrap()).unwrap();
+        write(
+            release_dir.join("SHA256SUMS"),
+            format!("{}  {}\n", sha256, file_name),
+        )
+        .unwrap();
+        let mirror_url = Url::from_directory_path(mirror_dir.path()).unwrap();
+        write(
+            dot_dir.etc.join("config.toml"),
+            format!("[mirrors]\nterragrunt = [\"{}\"]\n", mirror_url),
+        )
+        .unwrap();
+        let bundle_path = home.path().join("bundle.tar");
+        export_bundle(
+            vec![(Binary::Terragrunt, vec!["0.38.4".to_string()])],
+            dot_dir,
+            os.clone(),
+            arch.clone(),
+            &bundle_path,
+        )
+        .unwrap();
+
+        let import_home = tempdir().unwrap();
+        let import_dot_dir = DotDir::bootstrap(import_home.path()).unwrap();
+        import_bundle(
+            &bundle_path,
+            import_dot_dir.clone(),
+            os.clone(),
+            arch.clone(),
+        )
+        .unwrap();
+        assert!(import_dot_dir.opt.join("terragrunt/0.38.4").exists());
+
+        let bundle_dir = tempdir().unwrap();
+        extract_bundle(&File::open(&bundle_path).unwrap(), bundle_dir.path()).unwrap();
+        let cache_dir = bundle_dir.path().join(BUNDLE_CACHE_DIR_NAME);
+        let evil_content = "#!/bin/sh\necho evil\n";
+        let evil_blob_path = cache_dir.join("blobs").join("evil");
+        write(&evil_blob_path, evil_content).unwrap();
+        let evil_sha256 = sha256_hex(&File::open(&evil_blob_path).unwrap()).unwrap();
+        std::fs::rename(&evil_blob_path, cache_dir.join("blobs").join(&evil_sha256)).unwrap();
+        for entry in read_dir(cache_dir.join("index")).unwrap() {
+            let index_path = entry.unwrap().path();
+            let index_entry = read_to_string(&index_path).unwrap();
+            if let Some(url) = index_entry.trim().strip_prefix(&format!("{} ", sha256)) {
+                write(&index_path, format!("{} {}\n", evil_sha256, url)).unwrap();
+            }
+        }
+        let tampered_bundle_path = home.path().join("tampered.tar");
+        {
+            let mut builder = Builder::new(File::create(&tampered_bundle_path).unwrap());
+            builder
+                .append_path_with_name(
+                    bundle_dir.path().join(MANIFEST_FILE_NAME),
+                    MANIFEST_FILE_NAME,
+                )
+                .unwrap();
+            builder
+                .append_dir_all(BUNDLE_CACHE_DIR_NAME, &cache_dir)
+                .unwrap();
+            builder.finish().unwrap();
+        }
+        let tampered_home = tempdir().unwrap();
+        let tampered_dot_dir = DotDir::bootstrap(tampered_home.path()).unwrap();
+        let err = import_bundle(&tampered_bundle_path, tampered_dot_dir.clone(), os, arch)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("checksum mismatch"), "{}", err);
+        assert!(!tampered_dot_dir.opt.join("terragrunt/0.38.4").exists());
+    }
+
+    #[test]
+    fn test_is_cache_file_path() {
+        let sha256 = "a".repeat(64);
+        assert!(is_cache_file_path(&Path::new("cache/blobs").join(&sha256)));
+        assert!(is_cache_file_path(&Path::new("cache/index").join(&sha256)));
+        assert!(!is_cache_file_path(&Path::new("cache/other").join(&sha256)));
+        assert!(!is_cache_file_path(Path::new("cache/blobs/terraform")));
+        assert!(!is_cache_file_path(Path::new("opt/terraform/1.5.7")));
+    }
+
+    #[test]
+    fn test_extract_bundle_rejects_unexpected_entries() {
+        let mut builder = Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "opt/terraform/1.5.7", &b"evil"[..])
+            .unwrap();
+        let mut bundle_file = tempfile::tempfile().unwrap();
+        bundle_file
+            .write_all(&builder.into_inner().unwrap())
+            .unwrap();
+        bundle_file.seek(SeekFrom::Start(0)).unwrap();
+        let dest_dir = tempfile::tempdir().unwrap();
+        assert!(extract_bundle(&bundle_file, dest_dir.path()).is_err());
+        assert!(!dest_dir.path().join("opt").exists());
+    }
+}
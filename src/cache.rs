@@ -0,0 +1,405 @@
+use std::{
+    error::Error,
+    fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, remove_file, File},
+    io::{copy, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::Config,
+    http::HttpClient,
+    shared::{DotDir, InstallOptions},
+    utils::{sha256_hex, wprintln, write_file_atomically, NEWLINE},
+};
+
+// Downloads release artifacts (archive, checksum file, signature), reusing
+// cached copies. Cached files are content-addressed: blobs/<sha256> holds the
+// content, and index/<sha256 of url> the sha256 of the content for an URL.
+// Downloaded files are only cached once the installer has verified them,
+// by calling commit. With mirrors, files are fetched from each mirror in turn
+// but cached by their canonical release URL.
+pub struct Downloader {
+    cache_dir: PathBuf,
+    offline: bool,
+    http_client: Option<HttpClient>,
+    localhost_http_client: Option<HttpClient>,
+    config: Config,
+    releases_url: String,
+    mirrors: Vec<String>,
+    downloaded: Vec<(String, File)>,
+}
+
+impl Downloader {
+    pub fn new(dot_dir: &DotDir, options: &InstallOptions) -> Result<Downloader, Box<dyn Error>> {
+        Ok(Downloader {
+            cache_dir: options
+                .cache_dir
+                .clone()
+                .unwrap_or_else(|| get_cache_dir(dot_dir)),
+            offline: options.offline,
+            http_client: None,
+            localhost_http_client: None,
+            config: Config::load(dot_dir)?,
+            releases_url: String::new(),
+            mirrors: Vec::new(),
+            downloaded: Vec::new(),
+        })
+    }
+
+    // Fetches URLs under releases_url from configured mirrors of given binary
+    pub fn with_mirrors(
+        mut self,
+        binary_name: &str,
+        releases_url: &str,
+    ) -> Result<Downloader, Box<dyn Error>> {
+        let mirrors = self.config.get_mirrors(binary_name);
+        for mirror in &mirrors {
+            check_mirror_url(mirror, self.config.allow_http)?;
+        }
+        self.releases_url = releases_url.to_string();
+        self.mirrors = mirrors;
+        Ok(self)
+    }
+
+    pub fn download_file(&mut self, url: &str, mut dest_file: &File) -> Result<(), Box<dyn Error>> {
+        dest_file.set_len(0)?;
+        dest_file.seek(SeekFrom::Start(0))?;
+        if let Some(mut cached_file) = self.open_cached_file(url)? {
+            copy(&mut cached_file, &mut dest_file)?;
+            dest_file.seek(SeekFrom::Start(0))?;
+            return Ok(());
+        }
+        if self.offline {
+            return Err(format!(
+                "{} is not cached, unable to download it in offline mode",
+                url
+            )
+            .into());
+        }
+        let source_urls = self.get_source_urls(url);
+        for (i, source_url) in source_urls.iter().enumerate() {
+            dest_file.set_len(0)?;
+            dest_file.seek(SeekFrom::Start(0))?;
+            match self.fetch(source_url, dest_file) {
+                Ok(()) => break,
+                Err(e) if i + 1 < source_urls.len() => {
+                    wprintln(&format!("Unable to download {}: {}", source_url, e));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        dest_file.seek(SeekFrom::Start(0))?;
+        self.downloaded
+            .push((url.to_string(), dest_file.try_clone()?));
+        Ok(())
+    }
+
+    fn get_source_urls(&self, url: &str) -> Vec<String> {
+        match url.strip_prefix(&self.releases_url) {
+            Some(path) if !self.mirrors.is_empty() && !self.releases_url.is_empty() => self
+                .mirrors
+                .iter()
+                .map(|m| format!("{}{}", m.trim_end_matches('/'), path))
+                .collect(),
+            _ => vec![url.to_string()],
+        }
+    }
+
+    fn fetch(&mut self, url: &str, mut dest_file: &File) -> Result<(), Box<dyn Error>> {
+        if url.starts_with("file://") {
+            let file_path = Url::parse(url)?
+                .to_file_path()
+                .map_err(|_| format!("Invalid file URL {}", url))?;
+            copy(&mut File::open(file_path)?, &mut dest_file)?;
+            return Ok(());
+        }
+        // Only http:// mirror URLs (on localhost, see check_mirror_url) get a
+        // client that allows plain http
+        let http_client = if url.starts_with("http://") {
+            if self.localhost_http_client.is_none() {
+                self.localhost_http_client = Some(HttpClient::new_allow_http(&self.config.http)?);
+            }
+            self.localhost_http_client.as_ref()
+        } else {
+            if self.http_client.is_none() {
+                self.http_client = Some(HttpClient::new(&self.config.http)?);
+            }
+            self.http_client.as_ref()
+        };
+        if let Some(http_client) = http_client {
+            http_client.download_file(url, dest_file)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_bytes(&mut self, url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut tmp_file = tempfile::tempfile()?;
+        self.download_file(url, &tmp_file)?;
+        let mut bytes = Vec::new();
+        tmp_file.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn get_text(&mut self, url: &str) -> Result<String, Box<dyn Error>> {
+        let text = String::from_utf8(self.get_bytes(url)?)?;
+        Ok(text)
+    }
+
+    // Stores downloaded files in cache, call once they have been verified
+    pub fn commit(self) -> Result<(), Box<dyn Error>> {
+        for (url, mut file) in self.downloaded {
+            let sha256 = sha256_hex(&file)?;
+            let blob_file_path = self.cache_dir.join(BLOBS_DIR_NAME).join(&sha256);
+            if !blob_file_path.exists() {
+                create_dir_all(self.cache_dir.join(BLOBS_DIR_NAME))?;
+                write_file_atomically(&blob_file_path, &mut file, false)?;
+            }
+            let index_file_path = self.cache_dir.join(INDEX_DIR_NAME).join(url_key(&url));
+            create_dir_all(self.cache_dir.join(INDEX_DIR_NAME))?;
+            let index_entry = format!("{} {}\n", sha256, url);
+            write_file_atomically(&index_file_path, &mut index_entry.as_bytes(), false)?;
+        }
+        Ok(())
+    }
+
+    // Cached file is re-verified against its content address, a corrupt file
+    // is removed and downloaded again
+    fn open_cached_file(&self, url: &str) -> Result<Option<File>, Box<dyn Error>> {
+        let index_file_path = self.cache_dir.join(INDEX_DIR_NAME).join(url_key(url));
+        let sha256 = match read_index_entry(&index_file_path) {
+            Some((sha256, _)) => sha256,
+            None => return Ok(None),
+        };
+        let blob_file_path = self.cache_dir.join(BLOBS_DIR_NAME).join(&sha256);
+        if !blob_file_path.is_file() {
+            return Ok(None);
+        }
+        let blob_file = File::open(&blob_file_path)?;
+        if sha256_hex(&blob_file)? != sha256 {
+            wprintln(&format!("Removing corrupt cached file for {}", url));
+            drop(blob_file);
+            remove_file(&blob_file_path)?;
+            remove_file(&index_file_path)?;
+            return Ok(None);
+        }
+        Ok(Some(blob_file))
+    }
+}
+
+pub fn list_cached_files(dot_dir: DotDir) -> Result<String, Box<dyn Error>> {
+    let cache_dir = get_cache_dir(&dot_dir);
+    let mut lines: Vec<String> = get_index_entries(&cache_dir)?
+        .into_iter()
+        .filter_map(|(sha256, url)| {
+            let size = cache_dir
+                .join(BLOBS_DIR_NAME)
+                .join(sha256)
+                .metadata()
+                .ok()?
+                .len();
+            Some(format!("{} ({})", url, format_size(size)))
+        })
+        .collect();
+    lines.sort();
+    Ok(lines.join(NEWLINE))
+}
+
+pub fn get_cache_size(dot_dir: DotDir) -> Result<String, Box<dyn Error>> {
+    let (count, size) = get_blobs_count_and_size(&get_cache_dir(&dot_dir))?;
+    Ok(format!("{} ({} files)", format_size(size), count))
+}
+
+pub fn clean_cache(dot_dir: DotDir) -> Result<String, Box<dyn Error>> {
+    let cache_dir = get_cache_dir(&dot_dir);
+    let (count, size) = get_blobs_count_and_size(&cache_dir)?;
+    for dir_name in [INDEX_DIR_NAME, BLOBS_DIR_NAME] {
+        let dir = cache_dir.join(dir_name);
+        if dir.exists() {
+            remove_dir_all(&dir)?;
+        }
+    }
+    Ok(format!(
+        "Removed {} cached files ({})",
+        count,
+        format_size(size)
+    ))
+}
+
+// Mirrors must use https, file, or http on localhost if allowed in config
+pub fn check_mirror_url(mirror: &str, allow_http: bool) -> Result<(), Box<dyn Error>> {
+    let url = Url::parse(mirror).map_err(|e| format!("Invalid mirror URL {}: {}", mirror, e))?;
+    match url.scheme() {
+        "https" | "file" => Ok(()),
+        "http"
+            if allow_http
+                && matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")) =>
+        {
+            Ok(())
+        }
+        "http" => Err(format!(
+            "Insecure mirror URL {}, http is only allowed for localhost with allow_http = true",
+            mirror
+        )
+        .into()),
+        _ => Err(format!("Unsupported mirror URL {}", mirror).into()),
+    }
+}
+
+fn get_cache_dir(dot_dir: &DotDir) -> PathBuf {
+    dot_dir.var.join(CACHE_DIR_NAME)
+}
+
+fn url_key(url: &str) -> String {
+    hex::encode(Sha256::digest(url.as_bytes()))
+}
+
+fn read_index_entry(index_file_path: &Path) -> Option<(String, String)> {
+    let entry = read_to_string(index_file_path).ok()?;
+    let (sha256, url) = entry.trim().split_once(' ')?;
+    Some((sha256.to_string(), url.to_string()))
+}
+
+fn get_index_entries(cache_dir: &Path) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let index_dir = cache_dir.join(INDEX_DIR_NAME);
+    if !index_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let entries = read_dir(&index_dir)?
+        .filter_map(|r| Some(r.ok()?.path()))
+        .filter_map(|p| read_index_entry(&p))
+        .collect();
+    Ok(entries)
+}
+
+fn get_blobs_count_and_size(cache_dir: &Path) -> Result<(u64, u64), Box<dyn Error>> {
+    let blobs_dir = cache_dir.join(BLOBS_DIR_NAME);
+    if !blobs_dir.is_dir() {
+        return Ok((0, 0));
+    }
+    let mut count = 0;
+    let mut size = 0;
+    for entry in read_dir(&blobs_dir)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            count += 1;
+            size += metadata.len();
+        }
+    }
+    Ok((count, size))
+}
+
+fn format_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, units[0])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+const CACHE_DIR_NAME: &str = "cache";
+
+const BLOBS_DIR_NAME: &str = "blobs";
+
+const INDEX_DIR_NAME: &str = "index";
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Write;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_downloader_offline() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let url = "https://releases.hashicorp.com/terraform/1.5.7/terraform_1.5.7_SHA256SUMS";
+        let options = InstallOptions {
+            offline: true,
+            ..Default::default()
+        };
+        let mut downloader = Downloader::new(&dot_dir, &options).unwrap();
+        assert!(downloader.get_text(url).is_err());
+
+        // Simulate a verified download
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"shasums").unwrap();
+        downloader.downloaded.push((url.to_string(), file));
+        downloader.commit().unwrap();
+
+        let options = InstallOptions {
+            offline: true,
+            ..Default::default()
+        };
+        let mut downloader = Downloader::new(&dot_dir, &options).unwrap();
+        assert_eq!(downloader.get_text(url).unwrap(), "shasums");
+        assert!(list_cached_files(dot_dir.clone()).unwrap().starts_with(url));
+        assert_eq!(get_cache_size(dot_dir.clone()).unwrap(), "7 B (1 files)");
+
+        // Tampered cached file is not reused
+        let blob_file_path = get_cache_dir(&dot_dir)
+            .join(BLOBS_DIR_NAME)
+            .join(hex::encode(Sha256::digest(b"shasums")));
+        std::fs::write(&blob_file_path, "tampered").unwrap();
+        assert!(downloader.get_text(url).is_err());
+        assert!(!blob_file_path.exists());
+
+        assert_eq!(
+            clean_cache(dot_dir.clone()).unwrap(),
+            "Removed 0 cached files (0 B)"
+        );
+        assert_eq!(list_cached_files(dot_dir).unwrap(), "");
+    }
+
+    #[test]
+    fn test_downloader_mirrors() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let mirror_dir = tempdir().unwrap();
+        std::fs::create_dir_all(mirror_dir.path().join("1.5.7")).unwrap();
+        std::fs::write(
+            mirror_dir.path().join("1.5.7/terraform_1.5.7_SHA256SUMS"),
+            "shasums",
+        )
+        .unwrap();
+        let missing_mirror = Url::from_directory_path(home.path().join("missing")).unwrap();
+        let mirror = Url::from_directory_path(mirror_dir.path()).unwrap();
+        let mut downloader = Downloader::new(&dot_dir, &InstallOptions::default()).unwrap();
+        downloader.releases_url = "https://releases.hashicorp.com/terraform".to_string();
+        downloader.mirrors = vec![missing_mirror.to_string(), mirror.to_string()];
+        let url = "https://releases.hashicorp.com/terraform/1.5.7/terraform_1.5.7_SHA256SUMS";
+        assert_eq!(downloader.get_text(url).unwrap(), "shasums");
+        downloader.commit().unwrap();
+        assert!(list_cached_files(dot_dir).unwrap().starts_with(url));
+    }
+
+    #[test]
+    fn test_check_mirror_url() {
+        assert!(check_mirror_url("https://mirror.example.com/terraform", false).is_ok());
+        assert!(check_mirror_url("file:///srv/mirror/terraform", false).is_ok());
+        assert!(check_mirror_url("http://localhost:8080/terraform", true).is_ok());
+        assert!(check_mirror_url("http://[::1]:8080/terraform", true).is_ok());
+        assert!(check_mirror_url("http://localhost:8080/terraform", false).is_err());
+        assert!(check_mirror_url("http://mirror.example.com/terraform", true).is_err());
+        assert!(check_mirror_url("ftp://mirror.example.com/terraform", false).is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(25 * 1024 * 1024), "25.0 MiB");
+    }
+}
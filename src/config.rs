@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    env::var,
+    error::Error,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::shared::DotDir;
+
+// Optional configuration file etc/config.toml, e.g.
+//
+//   allow_http = true
+//
+//   [mirrors]
+//   terraform = ["https://nexus.example.com/repository/hashicorp/terraform", "https://releases.hashicorp.com/terraform"]
+//   terragrunt = ["file:///srv/mirror/terragrunt"]
+//
+//   [http]
+//   proxy = "http://proxy.example.com:3128"
+//   no_proxy = "localhost,.example.com"
+//   ca_certificates = ["/etc/ssl/certs/corporate-root.pem"]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Allows http:// mirrors on localhost
+    pub allow_http: bool,
+    // Release download base URLs per binary, tried in order
+    pub mirrors: HashMap<String, Vec<String>>,
+    pub http: HttpConfig,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    // Proxy URL, defaults to HTTPS_PROXY (or ALL_PROXY) environment variable
+    pub proxy: Option<String>,
+    // Comma separated hosts or domains not to proxy, defaults to NO_PROXY
+    pub no_proxy: Option<String>,
+    // Additional trusted CA certificates (PEM files)
+    pub ca_certificates: Vec<PathBuf>,
+    // Client certificate and private key (PEM file), for mutual TLS
+    pub client_identity: Option<PathBuf>,
+    // Bearer tokens per host
+    pub tokens: HashMap<String, String>,
+    // Whether to use credentials from ~/.netrc (or file set by NETRC)
+    pub netrc: bool,
+}
+
+pub enum Credentials {
+    Bearer(String),
+    Basic(String, String),
+}
+
+impl HttpConfig {
+    pub fn get_proxy(&self) -> Option<String> {
+        self.proxy
+            .clone()
+            .or_else(|| get_env_var(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]))
+            .filter(|p| !p.trim().is_empty())
+    }
+
+    // Hosts on loopback are never proxied
+    pub fn is_no_proxy(&self, host: &str) -> bool {
+        if matches!(host, "localhost" | "127.0.0.1" | "[::1]" | "::1") {
+            return true;
+        }
+        let no_proxy = self
+            .no_proxy
+            .clone()
+            .or_else(|| get_env_var(&["NO_PROXY", "no_proxy"]))
+            .unwrap_or_default();
+        no_proxy_matches(&no_proxy, host)
+    }
+
+    // Credentials are looked up from configured tokens, then GITHUB_TOKEN for
+    // GitHub hosts, then .netrc if enabled
+    pub fn get_credentials(&self, host: &str) -> Result<Option<Credentials>, Box<dyn Error>> {
+        if let Some(token) = self.tokens.get(host) {
+            return Ok(Some(Credentials::Bearer(token.clone())));
+        }
+        if GITHUB_HOSTS.contains(&host) {
+            if let Some(token) = get_env_var(&["GITHUB_TOKEN"]).filter(|t| !t.is_empty()) {
+                return Ok(Some(Credentials::Bearer(token)));
+            }
+        }
+        if self.netrc {
+            let netrc_path = match get_env_var(&["NETRC"]) {
+                Some(path) => PathBuf::from(path),
+                None => dirs::home_dir()
+                    .ok_or("Unable to resolve user home directory")?
+                    .join(NETRC_FILE_NAME),
+            };
+            return read_netrc_credentials(&netrc_path, host);
+        }
+        Ok(None)
+    }
+}
+
+impl Config {
+    pub fn load(dot_dir: &DotDir) -> Result<Config, Box<dyn Error>> {
+        let config_file_path = dot_dir.etc.join(CONFIG_FILE_NAME);
+        if !config_file_path.is_file() {
+            return Ok(Config::default());
+        }
+        let config = toml::from_str(&read_to_string(&config_file_path)?)
+            .map_err(|e| format!("Invalid {}: {}", config_file_path.display(), e))?;
+        Ok(config)
+    }
+
+    // Mirrors can be overridden with environment variable TERVE_<BINARY>_MIRRORS
+    // (comma separated), e.g. TERVE_TERRAFORM_MIRRORS
+    pub fn get_mirrors(&self, binary_name: &str) -> Vec<String> {
+        let env_var_name = format!(
+            "TERVE_{}_MIRRORS",
+            binary_name.to_uppercase().replace('-', "_")
+        );
+        match var(env_var_name) {
+            Ok(mirrors) if !mirrors.trim().is_empty() => mirrors
+                .split(',')
+                .map(|m| m.trim().to_string())
+                .filter(|m| !m.is_empty())
+                .collect(),
+            _ => self.mirrors.get(binary_name).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+fn get_env_var(names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| var(name).ok())
+}
+
+// Entries match the host itself or its subdomains, "*" matches all hosts
+fn no_proxy_matches(no_proxy: &str, host: &str) -> bool {
+    no_proxy
+        .split(',')
+        .map(|e| e.trim().trim_start_matches('.'))
+        .filter(|e| !e.is_empty())
+        .any(|e| e == "*" || host == e || host.ends_with(&format!(".{}", e)))
+}
+
+fn read_netrc_credentials(
+    netrc_path: &Path,
+    host: &str,
+) -> Result<Option<Credentials>, Box<dyn Error>> {
+    if !netrc_path.is_file() {
+        return Ok(None);
+    }
+    let netrc = read_to_string(netrc_path)?;
+    Ok(parse_netrc(&netrc, host).map(|(login, password)| Credentials::Basic(login, password)))
+}
+
+// Returns login and password of the matching machine. The default entry is
+// ignored, so that credentials are only sent to the host they are configured
+// for, see https://www.gnu.org/software/inetutils/manual/html_node/The-_002enetrc-file.html
+fn parse_netrc(netrc: &str, host: &str) -> Option<(String, String)> {
+    let mut tokens = netrc.split_whitespace();
+    let mut entries: Vec<(Option<String>, Option<String>, Option<String>)> = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => entries.push((tokens.next().map(String::from), None, None)),
+            "default" => entries.push((None, None, None)),
+            "login" | "password" => {
+                let value = tokens.next().map(String::from);
+                if let Some(entry) = entries.last_mut() {
+                    if token == "login" {
+                        entry.1 = value;
+                    } else {
+                        entry.2 = value;
+                    }
+                }
+            }
+            "account" | "macdef" => {
+                tokens.next();
+            }
+            _ => {}
+        }
+    }
+    entries
+        .iter()
+        .find(|(machine, _, _)| machine.as_deref() == Some(host))
+        .and_then(|(_, login, password)| Some((login.clone()?, password.clone()?)))
+}
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+const NETRC_FILE_NAME: &str = if cfg!(windows) { "_netrc" } else { ".netrc" };
+
+const GITHUB_HOSTS: [&str; 2] = ["github.com", "api.github.com"];
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::write;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_load_config() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        assert!(Config::load(&dot_dir).unwrap().mirrors.is_empty());
+        write(
+            dot_dir.etc.join(CONFIG_FILE_NAME),
+            "allow_http = true\n\n[mirrors]\nconsul = [\"file:///srv/mirror/consul\", \"http://localhost:8080/consul\"]\n",
+        )
+        .unwrap();
+        let config = Config::load(&dot_dir).unwrap();
+        assert!(config.allow_http);
+        assert_eq!(
+            config.get_mirrors("consul"),
+            vec!["file:///srv/mirror/consul", "http://localhost:8080/consul"]
+        );
+        assert!(config.get_mirrors("nomad").is_empty());
+        write(dot_dir.etc.join(CONFIG_FILE_NAME), "mirror = []\n").unwrap();
+        assert!(Config::load(&dot_dir).is_err());
+        write(
+            dot_dir.etc.join(CONFIG_FILE_NAME),
+            "[http]\nproxy = \"http://proxy.example.com:3128\"\nno_proxy = \".example.com\"\n\n[http.tokens]\n\"nexus.example.com\" = \"secret\"\n",
+        )
+        .unwrap();
+        let config = Config::load(&dot_dir).unwrap();
+        assert_eq!(
+            config.http.get_proxy().unwrap(),
+            "http://proxy.example.com:3128"
+        );
+        assert!(config.http.is_no_proxy("nexus.example.com"));
+        assert!(config.http.is_no_proxy("localhost"));
+        assert!(!config.http.is_no_proxy("releases.hashicorp.com"));
+        assert!(matches!(
+            config.http.get_credentials("nexus.example.com").unwrap(),
+            Some(Credentials::Bearer(t)) if t == "secret"
+        ));
+    }
+
+    #[test]
+    fn test_no_proxy_matches() {
+        assert!(no_proxy_matches("example.com", "example.com"));
+        assert!(no_proxy_matches("example.com", "nexus.example.com"));
+        assert!(no_proxy_matches(
+            " .example.com ,other.org",
+            "nexus.example.com"
+        ));
+        assert!(no_proxy_matches("*", "releases.hashicorp.com"));
+        assert!(!no_proxy_matches("example.com", "badexample.com"));
+        assert!(!no_proxy_matches("", "example.com"));
+    }
+
+    #[test]
+    fn test_parse_netrc() {
+        let netrc = "machine nexus.example.com\n  login alice\n  password s3cret\n\ndefault login anonymous password guest\n";
+        assert_eq!(
+            parse_netrc(netrc, "nexus.example.com").unwrap(),
+            ("alice".to_string(), "s3cret".to_string())
+        );
+        assert!(parse_netrc(netrc, "other.example.com").is_none());
+        // Default entry does not override the preceding machine
+        let netrc =
+            "machine nexus.example.com login alice password s3cret default login anonymous\n";
+        assert_eq!(
+            parse_netrc(netrc, "nexus.example.com").unwrap(),
+            ("alice".to_string(), "s3cret".to_string())
+        );
+        assert!(parse_netrc("machine a.example.com login x", "a.example.com").is_none());
+    }
+}
@@ -0,0 +1,243 @@
+use std::{cmp::Ordering, error::Error, fmt::Display, str::FromStr};
+
+use semver::{BuildMetadata, Prerelease, Version};
+
+// Implements terraform's version constraint semantics, see
+// https://www.terraform.io/language/expressions/version-constraints
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionConstraint {
+    clauses: Vec<Clause>,
+    raw: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Clause {
+    operator: Operator,
+    version: Version,
+    segments: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Pessimistic,
+}
+
+impl VersionConstraint {
+    pub fn matches(&self, version: &Version) -> bool {
+        self.clauses.iter().all(|c| c.matches(version))
+    }
+
+    pub fn resolve<'a>(&self, versions: &'a [Version]) -> Option<&'a Version> {
+        versions.iter().filter(|v| self.matches(v)).max()
+    }
+
+    pub fn resolve_min<'a>(&self, versions: &'a [Version]) -> Option<&'a Version> {
+        versions.iter().filter(|v| self.matches(v)).min()
+    }
+}
+
+impl Clause {
+    fn matches(&self, version: &Version) -> bool {
+        let c = &self.version;
+        match self.operator {
+            Operator::Equal => cmp_precedence(version, c) == Ordering::Equal,
+            Operator::NotEqual => cmp_precedence(version, c) != Ordering::Equal,
+            Operator::Greater => {
+                self.prerelease_check(version) && cmp_precedence(version, c).is_gt()
+            }
+            Operator::GreaterOrEqual => {
+                self.prerelease_check(version) && cmp_precedence(version, c).is_ge()
+            }
+            Operator::Less => self.prerelease_check(version) && cmp_precedence(version, c).is_lt(),
+            Operator::LessOrEqual => {
+                self.prerelease_check(version) && cmp_precedence(version, c).is_le()
+            }
+            Operator::Pessimistic => {
+                if !self.prerelease_check(version)
+                    || (c.pre != Prerelease::EMPTY && version.pre == Prerelease::EMPTY)
+                    || cmp_precedence(version, c).is_lt()
+                {
+                    return false;
+                }
+                // All but the last specified segment must match exactly,
+                // e.g. "~> 1.2" allows 1.x (x >= 2), "~> 1.2.3" allows 1.2.x (x >= 3)
+                let fixed = self.segments.saturating_sub(1).max(1);
+                let c_segments = [c.major, c.minor, c.patch];
+                let v_segments = [version.major, version.minor, version.patch];
+                c_segments[..fixed] == v_segments[..fixed]
+            }
+        }
+    }
+
+    // A prerelease version can only be matched by a constraint that names a
+    // prerelease of the same release, e.g. ">= 1.0.0-beta1" matches 1.0.0-rc1
+    fn prerelease_check(&self, version: &Version) -> bool {
+        let c = &self.version;
+        match (c.pre != Prerelease::EMPTY, version.pre != Prerelease::EMPTY) {
+            (true, true) => {
+                (c.major, c.minor, c.patch) == (version.major, version.minor, version.patch)
+            }
+            (false, true) => false,
+            _ => true,
+        }
+    }
+}
+
+// Build metadata does not take part in version precedence
+fn cmp_precedence(a: &Version, b: &Version) -> Ordering {
+    (a.major, a.minor, a.patch, &a.pre).cmp(&(b.major, b.minor, b.patch, &b.pre))
+}
+
+impl FromStr for VersionConstraint {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let clauses = s
+            .split(',')
+            .map(Clause::from_str)
+            .collect::<Result<Vec<Clause>, Self::Err>>()
+            .map_err(|e| format!("Invalid version constraint '{}': {}", s.trim(), e))?;
+        let raw = s.trim().to_string();
+        Ok(VersionConstraint { clauses, raw })
+    }
+}
+
+impl FromStr for Clause {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (operator, rest) = [
+            ("~>", Operator::Pessimistic),
+            (">=", Operator::GreaterOrEqual),
+            ("=>", Operator::GreaterOrEqual),
+            ("<=", Operator::LessOrEqual),
+            ("=<", Operator::LessOrEqual),
+            ("!=", Operator::NotEqual),
+            (">", Operator::Greater),
+            ("<", Operator::Less),
+            ("=", Operator::Equal),
+        ]
+        .iter()
+        .find_map(|(prefix, op)| s.strip_prefix(prefix).map(|r| (*op, r)))
+        .unwrap_or((Operator::Equal, s));
+        let (version, segments) = parse_partial_version(rest.trim())?;
+        Ok(Clause {
+            operator,
+            version,
+            segments,
+        })
+    }
+}
+
+// Parses possibly partial versions like "1", "1.5" or "v1.5.0-rc1", returning
+// the version (missing segments as zero) and the number of segments given
+fn parse_partial_version(s: &str) -> Result<(Version, usize), Box<dyn Error>> {
+    let s = s.strip_prefix('v').unwrap_or(s);
+    let (core, build) = match s.split_once('+') {
+        Some((c, b)) => (c, BuildMetadata::new(b)?),
+        None => (s, BuildMetadata::EMPTY),
+    };
+    let (core, pre) = match core.split_once('-') {
+        Some((c, p)) => (c, Prerelease::new(p)?),
+        None => (core, Prerelease::EMPTY),
+    };
+    let segments = core
+        .split('.')
+        .map(|n| n.parse::<u64>())
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| format!("'{}' is not a valid version", s))?;
+    if segments.is_empty() || segments.len() > 3 {
+        return Err(format!("'{}' is not a valid version", s).into());
+    }
+    let mut version = Version::new(
+        segments[0],
+        *segments.get(1).unwrap_or(&0),
+        *segments.get(2).unwrap_or(&0),
+    );
+    version.pre = pre;
+    version.build = build;
+    Ok((version, segments.len()))
+}
+
+impl Display for VersionConstraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn versions(v: &[&str]) -> Vec<Version> {
+        v.iter().map(|s| Version::parse(s).unwrap()).collect()
+    }
+
+    fn matches(constraint: &str, version: &str) -> bool {
+        VersionConstraint::from_str(constraint)
+            .unwrap()
+            .matches(&Version::parse(version).unwrap())
+    }
+
+    #[test]
+    fn test_pessimistic_constraint() {
+        assert!(matches("~> 1.5", "1.5.0"));
+        assert!(matches("~> 1.5", "1.9.2"));
+        assert!(!matches("~> 1.5", "2.0.0"));
+        assert!(!matches("~> 1.5", "1.4.9"));
+        assert!(matches("~> 1.5.2", "1.5.7"));
+        assert!(!matches("~> 1.5.2", "1.6.0"));
+        assert!(matches("~> 1", "1.9.0"));
+        assert!(!matches("~> 1", "2.0.0"));
+    }
+
+    #[test]
+    fn test_comma_joined_constraint() {
+        assert!(matches(">= 1.3, < 1.6", "1.3.0"));
+        assert!(matches(">= 1.3, < 1.6", "1.5.7"));
+        assert!(!matches(">= 1.3, < 1.6", "1.6.0"));
+        assert!(!matches(">= 1.3, < 1.6, != 1.4.2", "1.4.2"));
+        assert!(matches("1.2", "1.2.0"));
+        assert!(matches("= v1.2.3", "1.2.3"));
+    }
+
+    #[test]
+    fn test_prerelease_constraint() {
+        assert!(!matches(">= 1.0", "1.1.0-rc1"));
+        assert!(!matches("~> 1.0", "1.1.0-rc1"));
+        assert!(matches("1.1.0-rc1", "1.1.0-rc1"));
+        assert!(matches(">= 1.1.0-beta1", "1.1.0-rc1"));
+        assert!(!matches(">= 1.1.0-beta1", "1.2.0-rc1"));
+    }
+
+    #[test]
+    fn test_invalid_constraint() {
+        assert!(VersionConstraint::from_str("nope").is_err());
+        assert!(VersionConstraint::from_str(">= 1.2.3.4").is_err());
+        assert!(VersionConstraint::from_str(">= 1.0,").is_err());
+    }
+
+    #[test]
+    fn test_resolve_constraint() {
+        let available = versions(&["1.2.9", "1.5.0", "1.5.7", "1.6.0", "1.6.0-rc1"]);
+        let constraint = VersionConstraint::from_str("~> 1.5").unwrap();
+        assert_eq!(constraint.resolve(&available).unwrap().to_string(), "1.6.0");
+        let constraint = VersionConstraint::from_str(">= 1.3, < 1.6").unwrap();
+        assert_eq!(constraint.resolve(&available).unwrap().to_string(), "1.5.7");
+        assert_eq!(
+            constraint.resolve_min(&available).unwrap().to_string(),
+            "1.5.0"
+        );
+        let constraint = VersionConstraint::from_str("> 2").unwrap();
+        assert!(constraint.resolve(&available).is_none());
+    }
+}
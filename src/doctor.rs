@@ -0,0 +1,517 @@
+use std::{
+    env::{split_paths, var_os},
+    error::Error,
+    fs::{hard_link, read_to_string, remove_file},
+    path::{Path, PathBuf},
+};
+
+use reqwest::Url;
+
+use crate::{
+    cache::check_mirror_url,
+    config::Config,
+    hashicorp::HASHICORP_RELEASES_DOWNLOAD_URL,
+    http::HttpClient,
+    keyring::{get_keyring_status, Keyring},
+    shared::{Binary, DotDir, InstallOptions, BINARIES},
+    shim::is_shim_enabled,
+    tool::load_tool_descriptors,
+    utils::{create_tmp_file_for, println},
+};
+
+#[derive(Debug, PartialEq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    status: Status,
+    message: String,
+    // Suggested fix, for warnings and failures
+    fix: Option<String>,
+}
+
+impl Check {
+    fn pass(message: String) -> Check {
+        Check {
+            status: Status::Pass,
+            message,
+            fix: None,
+        }
+    }
+
+    fn warn(message: String, fix: String) -> Check {
+        Check {
+            status: Status::Warn,
+            message,
+            fix: Some(fix),
+        }
+    }
+
+    fn fail(message: String, fix: String) -> Check {
+        Check {
+            status: Status::Fail,
+            message,
+            fix: Some(fix),
+        }
+    }
+}
+
+// Diagnoses common environment problems: PATH, selected binaries, PGP keys,
+// filesystem layout and network access. Fails if any check fails.
+pub fn run_diagnostics(
+    dot_dir: DotDir,
+    options: &InstallOptions,
+) -> Result<String, Box<dyn Error>> {
+    let binaries: Vec<Binary> = BINARIES
+        .iter()
+        .cloned()
+        .chain(
+            load_tool_descriptors(&dot_dir)?
+                .into_iter()
+                .map(|t| Binary::Tool(Box::new(t))),
+        )
+        .collect();
+    let mut checks = Vec::new();
+    checks.extend(check_path(&dot_dir, &binaries));
+    checks.extend(check_selected_versions(&dot_dir, &binaries)?);
+    checks.extend(check_keyrings(&dot_dir));
+    checks.push(check_same_filesystem(&dot_dir));
+    if !options.offline {
+        checks.extend(check_network(&dot_dir, &binaries));
+    }
+    for check in &checks {
+        print_check(check);
+    }
+    if options.offline {
+        println("Skipped network checks in offline mode");
+    }
+    let failures = checks.iter().filter(|c| c.status == Status::Fail).count();
+    let warnings = checks.iter().filter(|c| c.status == Status::Warn).count();
+    if failures > 0 {
+        return Err(format!(
+            "{} of {} checks failed, {} warnings",
+            failures,
+            checks.len(),
+            warnings
+        )
+        .into());
+    }
+    if warnings > 0 {
+        return Ok(format!("No failed checks, {} warnings", warnings));
+    }
+    Ok("No problems found".to_string())
+}
+
+fn print_check(check: &Check) {
+    let label = match check.status {
+        Status::Pass => "PASS",
+        Status::Warn => "WARN",
+        Status::Fail => "FAIL",
+    };
+    println(&format!("[{}] {}", label, check.message));
+    if let Some(fix) = &check.fix {
+        println(&format!("       Fix: {}", fix));
+    }
+}
+
+// bin/ must be on PATH, ahead of other installs of the same binaries
+fn check_path(dot_dir: &DotDir, binaries: &[Binary]) -> Vec<Check> {
+    let paths: Vec<PathBuf> = match var_os("PATH") {
+        Some(path) => split_paths(&path).collect(),
+        None => Vec::new(),
+    };
+    if !paths.iter().any(|p| is_same_dir(p, &dot_dir.bin)) {
+        return vec![Check::fail(
+            format!("{} is not in PATH", dot_dir.bin.display()),
+            format!(
+                "Add {} to PATH, e.g. in your shell profile",
+                dot_dir.bin.display()
+            ),
+        )];
+    }
+    let mut checks = vec![Check::pass(format!("{} is in PATH", dot_dir.bin.display()))];
+    for binary in binaries {
+        if !dot_dir.bin.join(binary).exists() {
+            continue;
+        }
+        match find_in_path(binary.as_ref(), &paths) {
+            Some(file_path)
+                if file_path
+                    .parent()
+                    .is_some_and(|d| is_same_dir(d, &dot_dir.bin)) =>
+            {
+                checks.push(Check::pass(format!(
+                    "{} resolves to {}",
+                    binary,
+                    file_path.display()
+                )))
+            }
+            Some(file_path) => checks.push(Check::warn(
+                format!(
+                    "{} resolves to {}, ahead of {}",
+                    binary,
+                    file_path.display(),
+                    dot_dir.bin.display()
+                ),
+                format!(
+                    "Move {} before {} in PATH, or remove {}",
+                    dot_dir.bin.display(),
+                    file_path.parent().unwrap_or(&file_path).display(),
+                    file_path.display()
+                ),
+            )),
+            None => {}
+        }
+    }
+    checks
+}
+
+// First PATH directory containing given file
+fn find_in_path(file_name: &Path, paths: &[PathBuf]) -> Option<PathBuf> {
+    paths
+        .iter()
+        .map(|p| p.join(file_name))
+        .find(|p| p.is_file())
+}
+
+fn is_same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// bin/<binary> must still be a hard link to the selected opt/<binary>/<version>
+fn check_selected_versions(
+    dot_dir: &DotDir,
+    binaries: &[Binary],
+) -> Result<Vec<Check>, Box<dyn Error>> {
+    let mut checks = Vec::new();
+    for binary in binaries {
+        let version_file_path = dot_dir.var.join(binary).join("version");
+        if !version_file_path.is_file() {
+            continue;
+        }
+        let version = read_to_string(&version_file_path)?.trim().to_string();
+        let bin_file_path = dot_dir.bin.join(binary);
+        let opt_file_path = dot_dir.opt.join(binary).join(&version);
+        let select_fix = format!("Run 'terve s {} {}'", binary, version);
+        let check = if is_shim_enabled(binary, dot_dir) {
+            if bin_file_path.exists() {
+                Check::pass(format!("{} {} is selected (shim mode)", binary, version))
+            } else {
+                Check::fail(
+                    format!("{} is missing", bin_file_path.display()),
+                    format!("Run 'terve shim {}'", binary),
+                )
+            }
+        } else if !opt_file_path.exists() {
+            Check::fail(
+                format!("{} {} is selected, but not installed", binary, version),
+                format!("Run 'terve u {} {}'", binary, version),
+            )
+        } else if !bin_file_path.exists() {
+            Check::fail(
+                format!("{} is missing", bin_file_path.display()),
+                select_fix,
+            )
+        } else if !is_same_file(&bin_file_path, &opt_file_path)? {
+            Check::fail(
+                format!(
+                    "{} is not a hard link to {}",
+                    bin_file_path.display(),
+                    opt_file_path.display()
+                ),
+                select_fix,
+            )
+        } else {
+            Check::pass(format!("{} {} is selected", binary, version))
+        };
+        checks.push(check);
+    }
+    Ok(checks)
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> Result<bool, Box<dyn Error>> {
+    use std::os::unix::fs::MetadataExt;
+    let (a, b) = (a.metadata()?, b.metadata()?);
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+// File identity is not available on stable Rust for Windows, compare content
+#[cfg(not(unix))]
+fn is_same_file(a: &Path, b: &Path) -> Result<bool, Box<dyn Error>> {
+    use crate::utils::sha256_hex;
+    use std::fs::File;
+    Ok(sha256_hex(&File::open(a)?)? == sha256_hex(&File::open(b)?)?)
+}
+
+// Key files must be read-only, keys not expired, and opentofu needs a key
+// to verify downloads
+fn check_keyrings(dot_dir: &DotDir) -> Vec<Check> {
+    let mut checks = Vec::new();
+    for (keyring, binary) in [
+        (Keyring::HashiCorp, Binary::Terraform),
+        (Keyring::OpenTofu, Binary::OpenTofu),
+    ] {
+        let status = match get_keyring_status(dot_dir, keyring) {
+            Ok(status) => status,
+            Err(e) => {
+                checks.push(Check::fail(
+                    format!("Unable to load {} keyring: {}", keyring.name(), e),
+                    "Remove or replace the invalid key file".to_string(),
+                ));
+                continue;
+            }
+        };
+        for key_file_path in status.writable_key_files {
+            let fix = if cfg!(windows) {
+                format!("Run 'attrib +r {}'", key_file_path.display())
+            } else {
+                format!("Run 'chmod 444 {}'", key_file_path.display())
+            };
+            checks.push(Check::warn(
+                format!(
+                    "{} is ignored, it must be read-only",
+                    key_file_path.display()
+                ),
+                fix,
+            ));
+        }
+        for (key_id, expired_on) in status.expired_keys {
+            checks.push(Check::warn(
+                format!(
+                    "Key {} in {} keyring expired on {}",
+                    key_id,
+                    keyring.name(),
+                    expired_on
+                ),
+                format!("Add a current key with 'terve key add {} <FILE>'", binary),
+            ));
+        }
+        for key_id in status.revoked_keys {
+            checks.push(Check::warn(
+                format!(
+                    "Key {} in {} keyring has been revoked",
+                    key_id,
+                    keyring.name()
+                ),
+                format!("Run 'terve key remove {} {}'", binary, key_id),
+            ));
+        }
+        if status.key_count == 0 {
+            checks.push(Check::warn(
+                format!(
+                    "{} keyring is empty, {} installs fail signature verification",
+                    keyring.name(),
+                    binary
+                ),
+                format!(
+                    "Add the release signing key with 'terve key add {} <FILE>'",
+                    binary
+                ),
+            ));
+        } else {
+            checks.push(Check::pass(format!(
+                "{} keyring has {} keys",
+                keyring.name(),
+                status.key_count
+            )));
+        }
+    }
+    checks
+}
+
+// Selecting a version hard links opt/<binary>/<version> into bin/, which only
+// works within a filesystem
+fn check_same_filesystem(dot_dir: &DotDir) -> Check {
+    match try_hard_link(&dot_dir.opt, &dot_dir.bin) {
+        Ok(()) => Check::pass(format!(
+            "{} and {} are on the same filesystem",
+            dot_dir.opt.display(),
+            dot_dir.bin.display()
+        )),
+        Err(e) => Check::fail(
+            format!(
+                "Unable to hard link from {} to {}: {}",
+                dot_dir.opt.display(),
+                dot_dir.bin.display(),
+                e
+            ),
+            format!(
+                "Keep {} and {} on the same filesystem (e.g. do not mount or symlink only one of them)",
+                dot_dir.opt.display(),
+                dot_dir.bin.display()
+            ),
+        ),
+    }
+}
+
+fn try_hard_link(src_dir: &Path, dest_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let src_file = create_tmp_file_for(&src_dir.join("doctor"))?;
+    let dest_path = create_tmp_file_for(&dest_dir.join("doctor"))?.into_temp_path();
+    remove_file(&dest_path)?;
+    hard_link(src_file.path(), &dest_path)?;
+    Ok(())
+}
+
+// Release servers (for listing and downloads), and configured mirrors, must
+// be reachable
+fn check_network(dot_dir: &DotDir, binaries: &[Binary]) -> Vec<Check> {
+    let config = match Config::load(dot_dir) {
+        Ok(config) => config,
+        Err(e) => {
+            return vec![Check::fail(
+                e.to_string(),
+                "Correct the configuration file".to_string(),
+            )]
+        }
+    };
+    let mut urls = vec![
+        HASHICORP_RELEASES_DOWNLOAD_URL.to_string(),
+        GITHUB_URL.to_string(),
+    ];
+    let mut checks = Vec::new();
+    for binary in binaries {
+        for mirror in config.get_mirrors(&binary.to_string()) {
+            if let Err(e) = check_mirror_url(&mirror, config.allow_http) {
+                checks.push(Check::fail(
+                    e.to_string(),
+                    format!("Correct {} mirrors in the configuration file", binary),
+                ));
+            } else if !urls.contains(&mirror) {
+                urls.push(mirror);
+            }
+        }
+    }
+    // As when downloading, only http:// mirror URLs (on localhost, see
+    // check_mirror_url) get a client that allows plain http
+    let http_clients = HttpClient::new(&config.http)
+        .and_then(|c| Ok((c, HttpClient::new_allow_http(&config.http)?)));
+    let (http_client, localhost_http_client) = match http_clients {
+        Ok(http_clients) => http_clients,
+        Err(e) => {
+            checks.push(Check::fail(
+                format!("Unable to set up HTTP client: {}", e),
+                "Correct the http section of the configuration file".to_string(),
+            ));
+            return checks;
+        }
+    };
+    for url in urls {
+        if url.starts_with("http://") {
+            checks.push(check_url(&localhost_http_client, &url));
+        } else {
+            checks.push(check_url(&http_client, &url));
+        }
+    }
+    checks
+}
+
+// Any response means the server is reachable, also e.g. 404 for a mirror
+// base URL without a directory listing
+fn check_url(http_client: &HttpClient, url: &str) -> Check {
+    if let Some(dir) = Url::parse(url)
+        .ok()
+        .filter(|u| u.scheme() == "file")
+        .and_then(|u| u.to_file_path().ok())
+    {
+        return if dir.is_dir() {
+            Check::pass(format!("{} is a directory", dir.display()))
+        } else {
+            Check::fail(
+                format!("Mirror directory {} does not exist", dir.display()),
+                "Mount the mirror, or fix the mirror URL".to_string(),
+            )
+        };
+    }
+    match http_client.head(url) {
+        Ok(status) if status.as_u16() == 401 || status.as_u16() == 403 => Check::warn(
+            format!("{} responded with {}", url, status),
+            "Check credentials (tokens or netrc in the http section of the configuration file)"
+                .to_string(),
+        ),
+        Ok(status) if status.is_server_error() => Check::fail(
+            format!("{} responded with {}", url, status),
+            "Retry later, or use another mirror".to_string(),
+        ),
+        Ok(_) => Check::pass(format!("{} is reachable", url)),
+        Err(e) => Check::fail(
+            format!("{} is not reachable: {}", url, e),
+            "Check network connection and proxy settings (HTTPS_PROXY, or the http section of the configuration file), or use --offline".to_string(),
+        ),
+    }
+}
+
+const GITHUB_URL: &str = "https://github.com";
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::{copy, write};
+
+    use tempfile::tempdir;
+
+    use crate::shared::select_binary_version;
+
+    use super::*;
+
+    #[test]
+    fn test_find_in_path() {
+        let dir1 = tempdir().unwrap();
+        let dir2 = tempdir().unwrap();
+        write(dir2.path().join("terraform"), "").unwrap();
+        let paths = vec![dir1.path().to_path_buf(), dir2.path().to_path_buf()];
+        assert_eq!(
+            find_in_path(Path::new("terraform"), &paths).unwrap(),
+            dir2.path().join("terraform")
+        );
+        assert!(find_in_path(Path::new("terragrunt"), &paths).is_none());
+    }
+
+    #[test]
+    fn test_check_selected_versions() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let binaries = [Binary::Terragrunt];
+        assert!(check_selected_versions(&dot_dir, &binaries)
+            .unwrap()
+            .is_empty());
+        let opt_file_path = dot_dir.opt.join(Binary::Terragrunt).join("0.38.4");
+        write(&opt_file_path, "terragrunt").unwrap();
+        select_binary_version(Binary::Terragrunt, "0.38.4".to_string(), dot_dir.clone()).unwrap();
+        let checks = check_selected_versions(&dot_dir, &binaries).unwrap();
+        assert_eq!(checks[0].status, Status::Pass);
+
+        // A copy (e.g. restored from backup) is no longer a hard link
+        let bin_file_path = dot_dir.bin.join(Binary::Terragrunt);
+        remove_file(&bin_file_path).unwrap();
+        copy(&opt_file_path, &bin_file_path).unwrap();
+        let checks = check_selected_versions(&dot_dir, &binaries).unwrap();
+        assert_eq!(checks[0].status, Status::Fail);
+        assert_eq!(
+            checks[0].fix.as_deref(),
+            Some("Run 'terve s terragrunt 0.38.4'")
+        );
+
+        remove_file(&opt_file_path).unwrap();
+        let checks = check_selected_versions(&dot_dir, &binaries).unwrap();
+        assert_eq!(checks[0].status, Status::Fail);
+        assert_eq!(
+            checks[0].fix.as_deref(),
+            Some("Run 'terve u terragrunt 0.38.4'")
+        );
+    }
+
+    #[test]
+    fn test_check_same_filesystem() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        assert_eq!(check_same_filesystem(&dot_dir).status, Status::Pass);
+        assert_eq!(std::fs::read_dir(&dot_dir.bin).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    error::Error,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use semver::Version;
+
+use crate::{
+    shared::{
+        get_available_versions, get_installed_versions, install_binary_version, Binary, DotDir,
+        InstallOptions,
+    },
+    version::resolve_version,
+};
+
+type ExecEnvs = Vec<(&'static str, OsString)>;
+
+pub struct ExecOptions {
+    pub install: bool,
+    pub tf_version: Option<String>,
+    pub args: Vec<OsString>,
+}
+
+// Runs given version of binary without selecting it, optionally installing it
+// first. For terragrunt, TERRAGRUNT_TFPATH can be pointed at given terraform
+// version, see https://terragrunt.gruntwork.io/docs/reference/cli-options/#terragrunt-tfpath
+pub fn exec_binary_version(
+    binary: Binary,
+    version: String,
+    dot_dir: DotDir,
+    os: String,
+    arch: String,
+    options: ExecOptions,
+    install_options: &InstallOptions,
+) -> Result<String, Box<dyn Error>> {
+    let (opt_file_path, envs) = prepare_exec(
+        binary,
+        version,
+        &dot_dir,
+        &os,
+        &arch,
+        &options,
+        install_options,
+    )?;
+    let code = exec_binary(&opt_file_path, &options.args, &envs)?;
+    process::exit(code)
+}
+
+// Path of the binary to run, and the environment variables to pass it
+fn prepare_exec(
+    binary: Binary,
+    version: String,
+    dot_dir: &DotDir,
+    os: &str,
+    arch: &str,
+    options: &ExecOptions,
+    install_options: &InstallOptions,
+) -> Result<(PathBuf, ExecEnvs), Box<dyn Error>> {
+    let mut envs: ExecEnvs = Vec::new();
+    if let Some(tf_version) = options.tf_version.clone() {
+        if !matches!(binary, Binary::Terragrunt) {
+            return Err("Option --tf is only supported for terragrunt".into());
+        }
+        let tf_version = if Version::parse(&tf_version).is_ok() {
+            tf_version
+        } else {
+            // Same as the version argument: with --install, resolved against
+            // available versions, otherwise against installed versions
+            let versions = if options.install {
+                if install_options.offline {
+                    return Err("Unable to list available versions in offline mode".into());
+                }
+                get_available_versions(&Binary::Terraform, dot_dir, os, arch)?
+            } else {
+                get_installed_versions(&Binary::Terraform, dot_dir)?
+            };
+            resolve_version(&Binary::Terraform, &tf_version, &versions)?
+        };
+        let tf_file_path = get_opt_file_path(
+            Binary::Terraform,
+            tf_version,
+            dot_dir,
+            os,
+            arch,
+            options.install,
+            install_options,
+        )?;
+        envs.push(("TERRAGRUNT_TFPATH", tf_file_path.into_os_string()));
+    }
+    let opt_file_path = get_opt_file_path(
+        binary,
+        version,
+        dot_dir,
+        os,
+        arch,
+        options.install,
+        install_options,
+    )?;
+    Ok((opt_file_path, envs))
+}
+
+fn get_opt_file_path(
+    binary: Binary,
+    version: String,
+    dot_dir: &DotDir,
+    os: &str,
+    arch: &str,
+    install: bool,
+    install_options: &InstallOptions,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let opt_file_path = dot_dir.opt.join(&binary).join(&version);
+    if !opt_file_path.exists() {
+        if !install {
+            return Err(format!(
+                "{0} version {1} is not installed. Run 'terve i {0} {1}', or use --install",
+                binary, version
+            )
+            .into());
+        }
+        install_binary_version(
+            binary,
+            version,
+            dot_dir.clone(),
+            os.to_string(),
+            arch.to_string(),
+            install_options,
+        )?;
+    }
+    Ok(opt_file_path)
+}
+
+// Runs given binary in place of the current process, so that stdin/stdout,
+// signals and exit code are those of the binary. On Windows, where a process
+// cannot be replaced, the binary is run as a child process and its exit code
+// returned.
+pub fn exec_binary(
+    path: &Path,
+    args: &[OsString],
+    envs: &[(&str, OsString)],
+) -> Result<i32, Box<dyn Error>> {
+    let mut command = build_command(path, args, envs);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let e = command.exec();
+        Err(format!("Failed to execute {}: {}", path.display(), e).into())
+    }
+    #[cfg(windows)]
+    {
+        let status = command.status()?;
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+fn build_command(path: &Path, args: &[OsString], envs: &[(&str, OsString)]) -> Command {
+    let mut command = Command::new(path);
+    command.args(args);
+    command.envs(envs.iter().map(|(k, v)| (k, v)));
+    command
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{ffi::OsStr, fs::write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_prepare_exec() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let tf_file_path = dot_dir.opt.join(Binary::Terraform).join("1.2.3");
+        let tg_file_path = dot_dir.opt.join(Binary::Terragrunt).join("0.38.4");
+        write(&tf_file_path, "terraform").unwrap();
+        write(&tg_file_path, "terragrunt").unwrap();
+        let options = ExecOptions {
+            install: false,
+            tf_version: Some("~> 1.2".to_string()),
+            args: vec![],
+        };
+        let (path, envs) = prepare_exec(
+            Binary::Terragrunt,
+            "0.38.4".to_string(),
+            &dot_dir,
+            "linux",
+            "amd64",
+            &options,
+            &InstallOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(path, tg_file_path);
+        assert_eq!(
+            envs,
+            vec![("TERRAGRUNT_TFPATH", tf_file_path.into_os_string())]
+        );
+        assert!(prepare_exec(
+            Binary::Terraform,
+            "1.2.3".to_string(),
+            &dot_dir,
+            "linux",
+            "amd64",
+            &options,
+            &InstallOptions::default(),
+        )
+        .is_err());
+        let options = ExecOptions {
+            install: false,
+            tf_version: None,
+            args: vec![],
+        };
+        let err = prepare_exec(
+            Binary::Terragrunt,
+            "0.38.5".to_string(),
+            &dot_dir,
+            "linux",
+            "amd64",
+            &options,
+            &InstallOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("is not installed"));
+    }
+
+    #[test]
+    fn test_prepare_exec_install_resolves_available_versions() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        write(
+            dot_dir.opt.join(Binary::Terraform).join("1.2.3"),
+            "terraform",
+        )
+        .unwrap();
+        write(
+            dot_dir.opt.join(Binary::Terragrunt).join("0.38.4"),
+            "terragrunt",
+        )
+        .unwrap();
+        let options = ExecOptions {
+            install: true,
+            tf_version: Some("~> 1.2".to_string()),
+            args: vec![],
+        };
+        let install_options = InstallOptions {
+            offline: true,
+            ..Default::default()
+        };
+        // Not resolved to installed 1.2.3, available versions are needed
+        let err = prepare_exec(
+            Binary::Terragrunt,
+            "0.38.4".to_string(),
+            &dot_dir,
+            "linux",
+            "amd64",
+            &options,
+            &install_options,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("available versions"));
+        // Exact versions need no resolving
+        let options = ExecOptions {
+            tf_version: Some("1.2.3".to_string()),
+            ..options
+        };
+        let (_, envs) = prepare_exec(
+            Binary::Terragrunt,
+            "0.38.4".to_string(),
+            &dot_dir,
+            "linux",
+            "amd64",
+            &options,
+            &install_options,
+        )
+        .unwrap();
+        assert_eq!(envs.len(), 1);
+    }
+
+    #[test]
+    fn test_build_command() {
+        let args = vec![OsString::from("plan"), OsString::from("-out=plan.tfplan")];
+        let envs = vec![("TERRAGRUNT_TFPATH", OsString::from("/tmp/terraform"))];
+        let command = build_command(Path::new("/tmp/terragrunt"), &args, &envs);
+        assert_eq!(command.get_program(), "/tmp/terragrunt");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec![OsStr::new("plan"), OsStr::new("-out=plan.tfplan")]
+        );
+        assert_eq!(
+            command.get_envs().collect::<Vec<_>>(),
+            vec![(
+                OsStr::new("TERRAGRUNT_TFPATH"),
+                Some(OsStr::new("/tmp/terraform"))
+            )]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::error::Error;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{config::HttpConfig, http::HttpClient};
+
+#[derive(Deserialize)]
+struct Tag {
+    name: String,
+}
+
+// Lists tags of a GitHub repository via the REST API, following pagination
+// links, see https://docs.github.com/en/rest/repos/repos#list-repository-tags
+// (authenticated with GITHUB_TOKEN if set, for higher rate limits)
+pub fn list_tags(repo_url: &str, http_config: &HttpConfig) -> Result<Vec<String>, Box<dyn Error>> {
+    let (owner, repo) = parse_repo_url(repo_url)?;
+    let http_client = HttpClient::new(http_config)?;
+    let mut next_url = Some(format!(
+        "{}/repos/{}/{}/tags?per_page={}",
+        GITHUB_API_URL, owner, repo, TAGS_PER_PAGE
+    ));
+    let mut result = Vec::new();
+    while let Some(url) = next_url {
+        let (body, link) = http_client.get_json_page(&url)?;
+        let tags: Vec<Tag> = serde_json::from_str(&body)?;
+        result.extend(tags.into_iter().map(|t| t.name));
+        next_url = link.as_deref().and_then(parse_next_link);
+    }
+    Ok(result)
+}
+
+fn parse_repo_url(repo_url: &str) -> Result<(String, String), Box<dyn Error>> {
+    let regex = Regex::new(r"^https://github\.com/([^/]+)/([^/]+?)(\.git)?/?$")?;
+    let captures = regex.captures(repo_url).ok_or_else(|| {
+        format!(
+            "Unable to list tags of {}, only GitHub repositories are supported without git",
+            repo_url
+        )
+    })?;
+    Ok((captures[1].to_string(), captures[2].to_string()))
+}
+
+// Parses the next page URL from a Link header, e.g.
+// <https://api.github.com/repositories/1/tags?page=2>; rel="next", <...>; rel="last"
+fn parse_next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let mut fields = part.split(';');
+        let url = fields.next()?.trim();
+        let is_next = fields.any(|f| f.trim() == r#"rel="next""#);
+        if is_next && url.starts_with('<') && url.ends_with('>') {
+            Some(url[1..url.len() - 1].to_string())
+        } else {
+            None
+        }
+    })
+}
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+const TAGS_PER_PAGE: u32 = 100;
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_repo_url() {
+        let (owner, repo) = parse_repo_url("https://github.com/gruntwork-io/terragrunt").unwrap();
+        assert_eq!(owner, "gruntwork-io");
+        assert_eq!(repo, "terragrunt");
+        let (_, repo) = parse_repo_url("https://github.com/hashicorp/terraform.git").unwrap();
+        assert_eq!(repo, "terraform");
+        assert!(parse_repo_url("https://gitlab.com/foo/bar").is_err());
+    }
+
+    #[test]
+    fn test_parse_next_link() {
+        let link = r#"<https://api.github.com/repositories/59522149/tags?per_page=100&page=2>; rel="next", <https://api.github.com/repositories/59522149/tags?per_page=100&page=9>; rel="last""#;
+        assert_eq!(
+            parse_next_link(link).unwrap(),
+            "https://api.github.com/repositories/59522149/tags?per_page=100&page=2"
+        );
+        let link = r#"<https://api.github.com/repositories/59522149/tags?per_page=100&page=8>; rel="prev", <https://api.github.com/repositories/59522149/tags?per_page=100&page=1>; rel="first""#;
+        assert!(parse_next_link(link).is_none());
+    }
+}
@@ -0,0 +1,386 @@
+use std::{collections::HashMap, error::Error, fs::File, path::Path, str::FromStr};
+
+use pgp::{Deserializable, SignedPublicKey, StandaloneSignature};
+use semver::{BuildMetadata, Prerelease, Version};
+use serde::Deserialize;
+
+use crate::{
+    archive::{extract_binary_to_tmp_file, ArchiveType},
+    cache::Downloader,
+    config::HttpConfig,
+    http::HttpClient,
+    keyring::{get_key_id, load_keyring, verify_signature, Keyring},
+    manifest::{write_install_manifest, Signature, SignatureKey},
+    shared::{Binary, DotDir, InstallOptions},
+    utils::{check_sha256_sum, get_expected_sha256, persist_tmp_path, wprintln},
+    version_check::check_new_binary_version,
+};
+
+use std::env::consts::EXE_SUFFIX;
+
+// HashiCorp product other than terraform, released at releases.hashicorp.com
+#[derive(Debug)]
+pub struct HashiCorpProduct {
+    pub name: &'static str,
+    pub file_name: &'static str,
+    pub git_repository_url: &'static str,
+}
+
+pub const PACKER: HashiCorpProduct = HashiCorpProduct {
+    name: "packer",
+    file_name: if cfg!(unix) { "packer" } else { "packer.exe" },
+    git_repository_url: "https://github.com/hashicorp/packer",
+};
+
+pub const VAULT: HashiCorpProduct = HashiCorpProduct {
+    name: "vault",
+    file_name: if cfg!(unix) { "vault" } else { "vault.exe" },
+    git_repository_url: "https://github.com/hashicorp/vault",
+};
+
+pub const CONSUL: HashiCorpProduct = HashiCorpProduct {
+    name: "consul",
+    file_name: if cfg!(unix) { "consul" } else { "consul.exe" },
+    git_repository_url: "https://github.com/hashicorp/consul",
+};
+
+pub const NOMAD: HashiCorpProduct = HashiCorpProduct {
+    name: "nomad",
+    file_name: if cfg!(unix) { "nomad" } else { "nomad.exe" },
+    git_repository_url: "https://github.com/hashicorp/nomad",
+};
+
+pub const TERRAFORM_LS: HashiCorpProduct = HashiCorpProduct {
+    name: "terraform-ls",
+    file_name: if cfg!(unix) {
+        "terraform-ls"
+    } else {
+        "terraform-ls.exe"
+    },
+    git_repository_url: "https://github.com/hashicorp/terraform-ls",
+};
+
+// All HashiCorp products share the same release layout, e.g.
+// https://releases.hashicorp.com/packer/1.9.4/packer_1.9.4_linux_amd64.zip,
+// and SHA256SUMS files signed with HashiCorp's PGP keys
+pub fn install_product_version(
+    product: &str,
+    opt_file_path: &Path,
+    version: &str,
+    dot_dir: &DotDir,
+    os: &str,
+    arch: &str,
+    options: &InstallOptions,
+) -> Result<(), Box<dyn Error>> {
+    if !opt_file_path.exists() {
+        let zip_download_url = get_release_file_url(
+            product,
+            version,
+            &get_zip_file_name(product, version, os, arch),
+        );
+        let tmp_zip_file = tempfile::tempfile()?;
+        let releases_url = format!("{}/{}", HASHICORP_RELEASES_DOWNLOAD_URL, product);
+        let mut downloader =
+            Downloader::new(dot_dir, options)?.with_mirrors(product, &releases_url)?;
+        downloader.download_file(&zip_download_url, &tmp_zip_file)?;
+        // Without a keyring, the signature is not verified
+        let keyring = if options.skip_signature {
+            None
+        } else {
+            Some(load_keyring(dot_dir, Keyring::HashiCorp)?)
+        };
+        let signature_key = verify_download_integrity(
+            product,
+            version,
+            os,
+            arch,
+            keyring.as_deref(),
+            &mut downloader,
+            &tmp_zip_file,
+        )?;
+        let file_name = format!("{}{}", product, EXE_SUFFIX);
+        // Checked before it is put into place, so that no other process sees
+        // an unchecked binary in opt/
+        let tmp_opt_file = extract_binary_to_tmp_file(
+            tmp_zip_file.try_clone()?,
+            Some(ArchiveType::Zip),
+            &file_name,
+            opt_file_path,
+        )?;
+        check_new_binary_version(
+            &Binary::from_str(product)?,
+            &tmp_opt_file,
+            version,
+            os,
+            arch,
+        )?;
+        persist_tmp_path(tmp_opt_file, opt_file_path)?;
+        let signature = match signature_key {
+            Some(_) => Signature::Verified,
+            None => Signature::Skipped,
+        };
+        write_install_manifest(
+            dot_dir,
+            opt_file_path,
+            &tmp_zip_file,
+            signature,
+            signature_key,
+        )?;
+        downloader.commit()?;
+    }
+    Ok(())
+}
+
+fn verify_download_integrity(
+    product: &str,
+    version: &str,
+    os: &str,
+    arch: &str,
+    keyring: Option<&[SignedPublicKey]>,
+    downloader: &mut Downloader,
+    zip_file: &File,
+) -> Result<SignatureKey, Box<dyn Error>> {
+    let shasums_download_url =
+        get_release_file_url(product, version, &get_shasums_file_name(product, version));
+    let shasums = downloader.get_text(&shasums_download_url)?;
+    let signature_key = match keyring {
+        Some(keyring) => Some(verify_shasums_signature(
+            product, version, keyring, downloader, &shasums,
+        )?),
+        None => {
+            wprintln(&format!(
+                "Skipping PGP signature verification of {} {}",
+                product, version
+            ));
+            None
+        }
+    };
+    let expected_sha256 =
+        get_expected_sha256(&shasums, &get_zip_file_name(product, version, os, arch))?;
+    check_sha256_sum(zip_file, &expected_sha256)?;
+    Ok(signature_key)
+}
+
+// SHA256SUMS is signed separately with each current key, e.g.
+// terraform_1.5.7_SHA256SUMS.72D7468F.sig, a valid signature by any key of
+// the keyring is accepted. Returns the key ID of the verifying key.
+fn verify_shasums_signature(
+    product: &str,
+    version: &str,
+    keyring: &[SignedPublicKey],
+    downloader: &mut Downloader,
+    shasums: &str,
+) -> Result<String, Box<dyn Error>> {
+    let mut failures = Vec::new();
+    for public_key in keyring {
+        let pgp_key_id = get_key_id(public_key);
+        let shasums_sig_download_url = get_release_file_url(
+            product,
+            version,
+            &format!(
+                "{}.{}.sig",
+                get_shasums_file_name(product, version),
+                pgp_key_id
+            ),
+        );
+        let result = downloader
+            .get_bytes(&shasums_sig_download_url)
+            .and_then(|signature_bytes| {
+                let signature = StandaloneSignature::from_bytes(&signature_bytes[..])?;
+                verify_signature(shasums.as_bytes(), &signature, public_key)
+            });
+        match result {
+            Ok(()) => return Ok(pgp_key_id),
+            Err(e) => failures.push(format!("{}: {}", pgp_key_id, e)),
+        }
+    }
+    Err(format!(
+        "No valid PGP signature found for {} {} ({}). See https://github.com/superblk/terve#setup",
+        product,
+        version,
+        failures.join(", ")
+    )
+    .into())
+}
+
+fn get_zip_file_name(product: &str, version: &str, os: &str, arch: &str) -> String {
+    format!("{}_{}_{}_{}.zip", product, version, os, arch)
+}
+
+fn get_shasums_file_name(product: &str, version: &str) -> String {
+    format!("{}_{}_SHA256SUMS", product, version)
+}
+
+fn get_release_file_url(product: &str, version: &str, file_name: &str) -> String {
+    format!(
+        "{}/{}/{}/{}",
+        HASHICORP_RELEASES_DOWNLOAD_URL, product, version, file_name
+    )
+}
+
+// Lists versions that have a build for given os and arch, from the product's
+// release index, e.g. https://releases.hashicorp.com/terraform/index.json
+pub fn get_available_versions(
+    product: &str,
+    os: &str,
+    arch: &str,
+    http_config: &HttpConfig,
+) -> Result<Vec<Version>, Box<dyn Error>> {
+    let index_download_url = format!("{}/{}/index.json", HASHICORP_RELEASES_DOWNLOAD_URL, product);
+    let http_client = HttpClient::new(http_config)?;
+    let index = http_client.get_text(&index_download_url)?;
+    parse_release_index(&index, os, arch)
+}
+
+#[derive(Deserialize)]
+struct ReleaseIndex {
+    versions: HashMap<String, Release>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    builds: Vec<Build>,
+}
+
+#[derive(Deserialize)]
+struct Build {
+    os: String,
+    arch: String,
+}
+
+fn parse_release_index(index: &str, os: &str, arch: &str) -> Result<Vec<Version>, Box<dyn Error>> {
+    let index: ReleaseIndex = serde_json::from_str(index)?;
+    let versions: Vec<Version> = index
+        .versions
+        .iter()
+        .filter(|(_, r)| r.builds.iter().any(|b| b.os == os && b.arch == arch))
+        .filter_map(|(v, _)| Version::parse(v).ok())
+        .filter(|v| v.pre == Prerelease::EMPTY && v.build == BuildMetadata::EMPTY)
+        .collect();
+    Ok(versions)
+}
+
+pub const HASHICORP_RELEASES_DOWNLOAD_URL: &str = "https://releases.hashicorp.com";
+
+#[cfg(test)]
+mod tests {
+
+    use std::{
+        fs::{create_dir_all, read, write},
+        io::{Cursor, Write},
+    };
+
+    use reqwest::Url;
+    use tempfile::tempdir;
+    use zip::{write::FileOptions, ZipWriter};
+
+    use crate::{shared::get_os_arch, utils::sha256_hex};
+
+    use super::*;
+
+    #[test]
+    fn test_release_file_names() {
+        assert_eq!(
+            get_release_file_url(
+                "packer",
+                "1.9.4",
+                &get_zip_file_name("packer", "1.9.4", "linux", "amd64")
+            ),
+            "https://releases.hashicorp.com/packer/1.9.4/packer_1.9.4_linux_amd64.zip"
+        );
+        assert_eq!(
+            get_release_file_url(
+                "terraform-ls",
+                "0.32.3",
+                &get_shasums_file_name("terraform-ls", "0.32.3")
+            ),
+            "https://releases.hashicorp.com/terraform-ls/0.32.3/terraform-ls_0.32.3_SHA256SUMS"
+        );
+    }
+
+    #[test]
+    fn test_install_product_version_from_mirror() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let (os, arch) = get_os_arch();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(format!("packer{}", EXE_SUFFIX), FileOptions::default())
+            .unwrap();
+        writer.write_all(b"packer").unwrap();
+        let zip_bytes = writer.finish().unwrap().into_inner();
+        // Mirrors stand in for https://releases.hashicorp.com/<product>
+        let mirror_dir = tempdir().unwrap();
+        let release_dir = mirror_dir.path().join("1.9.4");
+        create_dir_all(&release_dir).unwrap();
+        let zip_file_path = release_dir.join(format!("packer_1.9.4_{}_{}.zip", os, arch));
+        write(&zip_file_path, &zip_bytes).unwrap();
+        let sha256 = sha256_hex(&File::open(&zip_file_path).unwrap()).unwrap();
+        write(
+            release_dir.join("packer_1.9.4_SHA256SUMS"),
+            format!("{}  packer_1.9.4_{}_{}.zip\n", sha256, os, arch),
+        )
+        .unwrap();
+        let mirror_url = Url::from_directory_path(mirror_dir.path()).unwrap();
+        write(
+            dot_dir.etc.join("config.toml"),
+            format!("[mirrors]\npacker = [\"{}\"]\n", mirror_url),
+        )
+        .unwrap();
+        let options = InstallOptions {
+            skip_signature: true,
+            ..Default::default()
+        };
+        let opt_file_path = dot_dir.opt.join("packer").join("1.9.4");
+        install_product_version(
+            "packer",
+            &opt_file_path,
+            "1.9.4",
+            &dot_dir,
+            &os,
+            &arch,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(read(&opt_file_path).unwrap(), b"packer");
+    }
+
+    #[test]
+    fn test_parse_release_index() {
+        let index = r#"{
+            "name": "terraform",
+            "versions": {
+                "0.11.15": {
+                    "name": "terraform",
+                    "version": "0.11.15",
+                    "builds": [
+                        {"os": "linux", "arch": "amd64", "filename": "terraform_0.11.15_linux_amd64.zip"},
+                        {"os": "linux", "arch": "arm64", "filename": "terraform_0.11.15_linux_arm64.zip"}
+                    ]
+                },
+                "0.11.14": {
+                    "name": "terraform",
+                    "version": "0.11.14",
+                    "builds": [
+                        {"os": "linux", "arch": "amd64", "filename": "terraform_0.11.14_linux_amd64.zip"}
+                    ]
+                },
+                "1.6.0-beta1": {
+                    "name": "terraform",
+                    "version": "1.6.0-beta1",
+                    "builds": [
+                        {"os": "linux", "arch": "arm64", "filename": "terraform_1.6.0-beta1_linux_arm64.zip"}
+                    ]
+                }
+            }
+        }"#;
+        let versions = parse_release_index(index, "linux", "arm64").unwrap();
+        assert_eq!(versions, vec![Version::parse("0.11.15").unwrap()]);
+        let mut versions = parse_release_index(index, "linux", "amd64").unwrap();
+        versions.sort();
+        assert_eq!(versions.len(), 2);
+        assert!(parse_release_index(index, "darwin", "arm64")
+            .unwrap()
+            .is_empty());
+    }
+}
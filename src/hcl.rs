@@ -0,0 +1,649 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+
+// Minimal HCL reader: parses the structure (attributes and blocks) of terraform
+// and terragrunt configuration files, but only evaluates string literals and
+// simple function calls, which is all we need for reading version constraints.
+// See https://github.com/hashicorp/hcl/blob/main/hclsyntax/spec.md
+
+#[derive(Debug)]
+pub struct HclError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for HclError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+impl Error for HclError {}
+
+#[derive(Debug, Default)]
+pub struct Body {
+    pub attributes: Vec<Attribute>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug)]
+pub struct Attribute {
+    pub name: String,
+    pub expr: Expr,
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub struct Block {
+    pub kind: String,
+    pub body: Body,
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Expr {
+    Literal(String),
+    Template(String),
+    Call(String, Vec<Expr>),
+    Other,
+}
+
+impl Body {
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    pub fn blocks<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a Block> {
+        self.blocks.iter().filter(move |b| b.kind == kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String, bool),
+    Punct(char),
+    Newline,
+}
+
+pub fn parse_file(path: &Path) -> Result<Body, HclError> {
+    let content = read_to_string(path).map_err(|e| HclError {
+        path: path.to_owned(),
+        line: 0,
+        message: e.to_string(),
+    })?;
+    parse(&content).map_err(|(line, message)| HclError {
+        path: path.to_owned(),
+        line,
+        message,
+    })
+}
+
+fn parse(content: &str) -> Result<Body, (usize, String)> {
+    let tokens = tokenize(content)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    parser.parse_body(false)
+}
+
+// Value of given attribute, which must be a string literal
+pub fn string_attribute(path: &Path, attribute: &Attribute) -> Result<String, HclError> {
+    match &attribute.expr {
+        Expr::Literal(s) => Ok(s.to_string()),
+        _ => Err(HclError {
+            path: path.to_owned(),
+            line: attribute.line,
+            message: format!("{} must be a string literal", attribute.name),
+        }),
+    }
+}
+
+// Collects required_version constraints of all terraform blocks in *.tf files,
+// see https://www.terraform.io/language/settings#specifying-a-required-terraform-version
+pub fn read_terraform_required_versions(dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut tf_file_paths: Vec<PathBuf> = read_dir(dir)?
+        .filter_map(|r| Some(r.ok()?.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "tf"))
+        .collect();
+    tf_file_paths.sort();
+    let mut constraints = Vec::new();
+    for path in tf_file_paths {
+        let body = parse_file(&path)?;
+        for block in body.blocks("terraform") {
+            if let Some(attribute) = block.body.attribute("required_version") {
+                constraints.push(string_attribute(&path, attribute)?);
+            }
+        }
+    }
+    Ok(constraints)
+}
+
+// Reads given attribute (e.g. terragrunt_version_constraint) from terragrunt.hcl
+// in given directory, following include blocks to parent configurations. As in
+// terragrunt, attributes of the including configuration take precedence.
+// See https://terragrunt.gruntwork.io/docs/reference/config-blocks-and-attributes/#include
+pub fn read_terragrunt_attribute(dir: &Path, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let mut path = dir.join(TERRAGRUNT_CONFIG_FILE_NAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let mut visited: Vec<PathBuf> = Vec::new();
+    let mut pending: Vec<PathBuf> = Vec::new();
+    loop {
+        let body = parse_file(&path)?;
+        if let Some(attribute) = body.attribute(name) {
+            return Ok(Some(string_attribute(&path, attribute)?));
+        }
+        visited.push(path.clone());
+        for include in body.blocks("include") {
+            let include_path = resolve_include_path(&path, include)?;
+            if !visited.contains(&include_path) && !pending.contains(&include_path) {
+                pending.push(include_path);
+            }
+        }
+        match pending.pop() {
+            Some(p) => path = p,
+            None => return Ok(None),
+        }
+    }
+}
+
+fn resolve_include_path(config_path: &Path, include: &Block) -> Result<PathBuf, HclError> {
+    let config_dir = config_path.parent().unwrap_or_else(|| Path::new("."));
+    let error = |line: usize, message: String| HclError {
+        path: config_path.to_owned(),
+        line,
+        message,
+    };
+    let attribute = include
+        .body
+        .attribute("path")
+        .ok_or_else(|| error(include.line, "include block has no path".to_string()))?;
+    let include_path = match &attribute.expr {
+        Expr::Literal(p) => config_dir.join(p),
+        Expr::Call(f, args) if f == "find_in_parent_folders" => {
+            let file_name = match args.first() {
+                Some(Expr::Literal(n)) => n.as_str(),
+                None => TERRAGRUNT_CONFIG_FILE_NAME,
+                Some(_) => {
+                    return Err(error(
+                        attribute.line,
+                        "find_in_parent_folders argument must be a string literal".to_string(),
+                    ))
+                }
+            };
+            config_dir
+                .ancestors()
+                .skip(1)
+                .map(|d| d.join(file_name))
+                .find(|p| p.is_file())
+                .ok_or_else(|| {
+                    error(
+                        attribute.line,
+                        format!("could not find {} in parent folders", file_name),
+                    )
+                })?
+        }
+        _ => {
+            return Err(error(
+                attribute.line,
+                "unsupported include path expression".to_string(),
+            ))
+        }
+    };
+    if !include_path.is_file() {
+        return Err(error(
+            attribute.line,
+            format!("included file {} not found", include_path.display()),
+        ));
+    }
+    Ok(include_path)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, l)| *l)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn parse_body(&mut self, nested: bool) -> Result<Body, (usize, String)> {
+        let mut body = Body::default();
+        loop {
+            let line = self.line();
+            match self.next() {
+                None if nested => return Err((line, "unclosed block, expected '}'".to_string())),
+                None => return Ok(body),
+                Some(Token::Newline) => continue,
+                Some(Token::Punct('}')) if nested => return Ok(body),
+                Some(Token::Ident(name)) => match self.peek() {
+                    Some(Token::Punct('=')) => {
+                        self.next();
+                        let expr = self.parse_expr(line)?;
+                        body.attributes.push(Attribute { name, expr, line });
+                    }
+                    _ => {
+                        // Block labels are skipped, we only look blocks up by type
+                        loop {
+                            match self.next() {
+                                Some(Token::Str(_, false)) | Some(Token::Ident(_)) => continue,
+                                Some(Token::Punct('{')) => break,
+                                _ => {
+                                    return Err((
+                                        line,
+                                        format!("expected '=' or block after '{}'", name),
+                                    ))
+                                }
+                            }
+                        }
+                        let body_ = self.parse_body(true)?;
+                        body.blocks.push(Block {
+                            kind: name,
+                            body: body_,
+                            line,
+                        });
+                    }
+                },
+                Some(other) => {
+                    return Err((line, format!("unexpected {}", describe(&other))));
+                }
+            }
+        }
+    }
+
+    // Expression runs until end of line (or end of enclosing block) outside brackets
+    fn parse_expr(&mut self, line: usize) -> Result<Expr, (usize, String)> {
+        let mut tokens = Vec::new();
+        let mut depth: usize = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Newline if depth == 0 => break,
+                Token::Punct('}') if depth == 0 => break,
+                Token::Punct('(' | '[' | '{') => depth += 1,
+                Token::Punct(c @ (')' | ']' | '}')) => {
+                    if depth == 0 {
+                        return Err((line, format!("unexpected '{}'", c)));
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+            tokens.push(self.next().unwrap_or(Token::Newline));
+        }
+        if depth > 0 {
+            return Err((line, "unclosed bracket in expression".to_string()));
+        }
+        if tokens.is_empty() {
+            return Err((line, "missing expression after '='".to_string()));
+        }
+        Ok(to_expr(&tokens))
+    }
+}
+
+fn to_expr(tokens: &[Token]) -> Expr {
+    let tokens: Vec<&Token> = tokens.iter().filter(|t| **t != Token::Newline).collect();
+    match tokens.as_slice() {
+        [Token::Str(s, false)] => Expr::Literal(s.to_string()),
+        [Token::Str(s, true)] => {
+            // "${f()}" is equivalent to f()
+            match s.strip_prefix("${").and_then(|s| s.strip_suffix('}')) {
+                Some(inner) if !inner.contains("${") => match tokenize(inner) {
+                    Ok(inner_tokens) => match to_expr(
+                        &inner_tokens
+                            .into_iter()
+                            .map(|(t, _)| t)
+                            .collect::<Vec<Token>>(),
+                    ) {
+                        call @ Expr::Call(_, _) => call,
+                        _ => Expr::Template(s.to_string()),
+                    },
+                    Err(_) => Expr::Template(s.to_string()),
+                },
+                _ => Expr::Template(s.to_string()),
+            }
+        }
+        [Token::Ident(f), Token::Punct('('), args @ .., Token::Punct(')')] => {
+            let mut exprs = Vec::new();
+            let mut depth: usize = 0;
+            let mut arg: Vec<Token> = Vec::new();
+            for token in args {
+                match token {
+                    Token::Punct(',') if depth == 0 => {
+                        exprs.push(to_expr(&arg));
+                        arg.clear();
+                        continue;
+                    }
+                    Token::Punct('(' | '[' | '{') => depth += 1,
+                    Token::Punct(')' | ']' | '}') => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                arg.push((*token).clone());
+            }
+            if !arg.is_empty() {
+                exprs.push(to_expr(&arg));
+            }
+            Expr::Call(f.to_string(), exprs)
+        }
+        _ => Expr::Other,
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(s) => format!("'{}'", s),
+        Token::Str(s, _) => format!("string \"{}\"", s),
+        Token::Punct(c) => format!("'{}'", c),
+        Token::Newline => "newline".to_string(),
+    }
+}
+
+fn tokenize(content: &str) -> Result<Vec<(Token, usize)>, (usize, String)> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => {
+                tokens.push((Token::Newline, line));
+                line += 1;
+                i += 1;
+            }
+            '#' => i = skip_line(&chars, i),
+            '/' if chars.get(i + 1) == Some(&'/') => i = skip_line(&chars, i),
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                let start_line = line;
+                i += 2;
+                loop {
+                    match chars.get(i) {
+                        None => return Err((start_line, "unterminated comment".to_string())),
+                        Some('*') if chars.get(i + 1) == Some(&'/') => {
+                            i += 2;
+                            break;
+                        }
+                        Some('\n') => line += 1,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+            }
+            '"' => {
+                let start_line = line;
+                let (s, is_template, end) = scan_string(&chars, i + 1, &mut line)
+                    .ok_or((start_line, "unterminated string".to_string()))?;
+                tokens.push((Token::Str(s, is_template), start_line));
+                i = end;
+            }
+            '<' if chars.get(i + 1) == Some(&'<') => {
+                let start_line = line;
+                let (s, end) = scan_heredoc(&chars, i + 2, &mut line)
+                    .ok_or((start_line, "unterminated heredoc".to_string()))?;
+                tokens.push((Token::Str(s, true), start_line));
+                i = end;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '-')
+                {
+                    i += 1;
+                }
+                let ident: String = chars[start..i].iter().collect();
+                tokens.push((Token::Ident(ident), line));
+            }
+            c if c.is_whitespace() => i += 1,
+            c => {
+                tokens.push((Token::Punct(c), line));
+                i += 1;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn skip_line(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && chars[i] != '\n' {
+        i += 1;
+    }
+    i
+}
+
+// Scans a quoted string starting after the opening quote, returning its
+// (unescaped) content, whether it contains interpolations, and end position
+fn scan_string(chars: &[char], mut i: usize, line: &mut usize) -> Option<(String, bool, usize)> {
+    let mut s = String::new();
+    let mut is_template = false;
+    loop {
+        match *chars.get(i)? {
+            '"' => return Some((s, is_template, i + 1)),
+            '\n' => return None,
+            '\\' => {
+                let escaped = *chars.get(i + 1)?;
+                s.push(match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    other => other,
+                });
+                i += 2;
+            }
+            c @ ('$' | '%') if chars.get(i + 1) == Some(&'{') => {
+                is_template = true;
+                s.push(c);
+                s.push('{');
+                i += 2;
+                let mut depth = 1;
+                while depth > 0 {
+                    match *chars.get(i)? {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        '\n' => *line += 1,
+                        '"' => {
+                            let (inner, _, end) = scan_string(chars, i + 1, line)?;
+                            s.push('"');
+                            s.push_str(&inner);
+                            s.push('"');
+                            i = end;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    s.push(chars[i]);
+                    i += 1;
+                }
+            }
+            c => {
+                s.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn scan_heredoc(chars: &[char], mut i: usize, line: &mut usize) -> Option<(String, usize)> {
+    if chars.get(i) == Some(&'-') {
+        i += 1;
+    }
+    let end_of_line = skip_line(chars, i);
+    let marker: String = chars[i..end_of_line].iter().collect::<String>();
+    let marker = marker.trim();
+    if marker.is_empty() {
+        return None;
+    }
+    let mut content = String::new();
+    i = end_of_line + 1;
+    *line += 1;
+    while i < chars.len() {
+        let end_of_line = skip_line(chars, i);
+        let text: String = chars[i..end_of_line].iter().collect();
+        if text.trim() == marker {
+            return Some((content, end_of_line));
+        }
+        content.push_str(&text);
+        content.push('\n');
+        i = end_of_line + 1;
+        *line += 1;
+    }
+    None
+}
+
+pub const TERRAGRUNT_CONFIG_FILE_NAME: &str = "terragrunt.hcl";
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::{create_dir_all, write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_terraform_block() {
+        let body = parse(
+            r#"
+            # comment
+            terraform {
+              required_version = ">= 1.3, < 1.6" // comment
+              required_providers {
+                aws = {
+                  source  = "hashicorp/aws"
+                  version = "~> 5.0"
+                }
+              }
+            }
+
+            resource "aws_s3_bucket" "this" { bucket = "${var.name}-bucket" }
+
+            /* multi
+               line */
+            locals {
+              tags = merge(var.tags, {
+                Name = "x"
+              })
+              policy = <<-EOF
+                {"Version": "2012-10-17"}
+              EOF
+            }
+            "#,
+        )
+        .unwrap();
+        let terraform = body.blocks("terraform").next().unwrap();
+        assert_eq!(
+            terraform.body.attribute("required_version").unwrap().expr,
+            Expr::Literal(">= 1.3, < 1.6".to_string())
+        );
+        assert_eq!(terraform.line, 3);
+        let resource = body.blocks("resource").next().unwrap();
+        assert_eq!(
+            resource.body.attribute("bucket").unwrap().expr,
+            Expr::Template("${var.name}-bucket".to_string())
+        );
+        let locals = body.blocks("locals").next().unwrap();
+        assert_eq!(locals.line, 17);
+        assert!(locals.body.attribute("policy").is_some());
+    }
+
+    #[test]
+    fn test_parse_error_line() {
+        let err = parse("terraform {\n  required_version = \"1.0\n}\n").unwrap_err();
+        assert_eq!(err.0, 2);
+        let err = parse("terraform {\n  required_version = \"1.0\"\n").unwrap_err();
+        assert_eq!(err.0, 2);
+        let err = parse("\n\nterraform ] {\n}\n").unwrap_err();
+        assert_eq!(err.0, 3);
+    }
+
+    #[test]
+    fn test_parse_stray_closing_bracket() {
+        assert_eq!(
+            parse("locals {\n  x = foo)\n}\n").unwrap_err(),
+            (2, "unexpected ')'".to_string())
+        );
+        assert_eq!(
+            parse("locals {\n  x = [1, 2]]\n}\n").unwrap_err(),
+            (2, "unexpected ']'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_terraform_required_versions() {
+        let dir = tempdir().unwrap();
+        write(
+            dir.path().join("versions.tf"),
+            "terraform {\n  required_version = \">= 1.3\"\n}\n",
+        )
+        .unwrap();
+        write(
+            dir.path().join("main.tf"),
+            "terraform {\n  backend \"s3\" {}\n}\nterraform { required_version = \"< 1.6\" }\n",
+        )
+        .unwrap();
+        let constraints = read_terraform_required_versions(dir.path()).unwrap();
+        assert_eq!(constraints, vec!["< 1.6", ">= 1.3"]);
+        write(
+            dir.path().join("broken.tf"),
+            "terraform {\n  required_version = var.x\n}\n",
+        )
+        .unwrap();
+        let err = read_terraform_required_versions(dir.path()).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("broken.tf:2: required_version must be a string literal"));
+    }
+
+    #[test]
+    fn test_read_terragrunt_attribute_include() {
+        let root = tempdir().unwrap();
+        let child = root.path().join("prod").join("vpc");
+        create_dir_all(&child).unwrap();
+        write(
+            root.path().join("terragrunt.hcl"),
+            "terragrunt_version_constraint = \">= 0.45\"\nterraform_version_constraint = \"~> 1.5\"\n",
+        )
+        .unwrap();
+        write(
+            root.path().join("prod").join("env.hcl"),
+            "include \"root\" {\n  path = find_in_parent_folders()\n}\n",
+        )
+        .unwrap();
+        write(
+            child.join("terragrunt.hcl"),
+            "include {\n  path = \"${find_in_parent_folders(\"env.hcl\")}\"\n}\nterraform_version_constraint = \"1.5.7\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_terragrunt_attribute(&child, "terraform_version_constraint").unwrap(),
+            Some("1.5.7".to_string())
+        );
+        assert_eq!(
+            read_terragrunt_attribute(&child, "terragrunt_version_constraint").unwrap(),
+            Some(">= 0.45".to_string())
+        );
+        assert_eq!(
+            read_terragrunt_attribute(root.path(), "nope").unwrap(),
+            None
+        );
+    }
+}
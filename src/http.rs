@@ -1,26 +1,99 @@
-use std::{fs::File, time::Duration};
+use std::{error::Error, fs::read, fs::File, time::Duration};
 
-use bytes::Bytes;
-use reqwest::blocking::Client;
+use reqwest::{
+    blocking::{Client, RequestBuilder},
+    redirect::Policy,
+    Certificate, Identity, Method, Proxy, StatusCode, Url,
+};
 
+use crate::config::{Credentials, HttpConfig};
+
+// Proxy, extra CA certificates, client identity and credentials are set up
+// from the http section of etc/config.toml
 pub struct HttpClient {
     client: Client,
+    http_config: HttpConfig,
 }
 
 impl HttpClient {
-    pub fn new() -> Result<HttpClient, reqwest::Error> {
-        let client = Client::builder()
+    pub fn new(http_config: &HttpConfig) -> Result<HttpClient, Box<dyn Error>> {
+        HttpClient::build(http_config, false)
+    }
+
+    // Also allows plain http, only for mirrors on localhost. Use for http://
+    // mirror URLs only, other requests must not be downgraded.
+    pub fn new_allow_http(http_config: &HttpConfig) -> Result<HttpClient, Box<dyn Error>> {
+        HttpClient::build(http_config, true)
+    }
+
+    fn build(http_config: &HttpConfig, allow_http: bool) -> Result<HttpClient, Box<dyn Error>> {
+        let mut builder = Client::builder()
             .user_agent(HTTP_USER_AGENT)
             .connect_timeout(CONNECT_TIMEOUT)
-            .https_only(true)
-            .build()?;
-        Ok(HttpClient { client })
+            .https_only(!allow_http)
+            .no_proxy();
+        if allow_http {
+            // Redirects must not leave localhost over plain http
+            builder = builder.redirect(Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if attempt.url().scheme() == "http"
+                    && !matches!(
+                        attempt.url().host_str(),
+                        Some("localhost" | "127.0.0.1" | "[::1]")
+                    )
+                {
+                    attempt.error("redirect to insecure http URL")
+                } else {
+                    attempt.follow()
+                }
+            }));
+        }
+        if let Some(proxy) = http_config.get_proxy() {
+            let proxy_url =
+                Url::parse(&proxy).map_err(|e| format!("Invalid proxy URL {}: {}", proxy, e))?;
+            let proxy_config = http_config.clone();
+            builder = builder.proxy(Proxy::custom(move |url| match url.host_str() {
+                Some(host) if proxy_config.is_no_proxy(host) => None,
+                _ => Some(proxy_url.clone()),
+            }));
+        }
+        for ca_certificate in &http_config.ca_certificates {
+            let pem = read(ca_certificate)
+                .map_err(|e| format!("Unable to read {}: {}", ca_certificate.display(), e))?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+        if let Some(client_identity) = &http_config.client_identity {
+            let pem = read(client_identity)
+                .map_err(|e| format!("Unable to read {}: {}", client_identity.display(), e))?;
+            builder = builder.identity(Identity::from_pem(&pem)?);
+        }
+        Ok(HttpClient {
+            client: builder.build()?,
+            http_config: http_config.clone(),
+        })
     }
 
-    pub fn download_file(&self, url: &str, mut dest_file: &File) -> Result<u64, reqwest::Error> {
+    // Credentials are only sent to their host, reqwest drops them on
+    // redirects to other hosts (e.g. GitHub release downloads)
+    fn get(&self, url: &str) -> Result<RequestBuilder, Box<dyn Error>> {
+        self.request(Method::GET, url)
+    }
+
+    fn request(&self, method: Method, url: &str) -> Result<RequestBuilder, Box<dyn Error>> {
+        let request = self.client.request(method, url);
+        let host = Url::parse(url)?.host_str().unwrap_or_default().to_string();
+        let request = match self.http_config.get_credentials(&host)? {
+            Some(Credentials::Bearer(token)) => request.bearer_auth(token),
+            Some(Credentials::Basic(login, password)) => request.basic_auth(login, Some(password)),
+            None => request,
+        };
+        Ok(request)
+    }
+
+    pub fn download_file(&self, url: &str, mut dest_file: &File) -> Result<u64, Box<dyn Error>> {
         let num_bytes = self
-            .client
-            .get(url)
+            .get(url)?
             .header("Accept", "application/octet-stream")
             .send()?
             .error_for_status()?
@@ -28,29 +101,43 @@ impl HttpClient {
         Ok(num_bytes)
     }
 
-    pub fn get_bytes(&self, url: &str) -> Result<Bytes, reqwest::Error> {
-        let bytes = self
-            .client
-            .get(url)
-            .header("Accept", "application/octet-stream")
-            .send()?
-            .error_for_status()?
-            .bytes()?;
-        Ok(bytes)
-    }
-
-    pub fn get_text(&self, url: &str) -> Result<String, reqwest::Error> {
+    pub fn get_text(&self, url: &str) -> Result<String, Box<dyn Error>> {
         let text = self
-            .client
-            .get(url)
+            .get(url)?
             .header("Accept", "text/plain")
             .send()?
             .error_for_status()?
             .text()?;
         Ok(text)
     }
+
+    // Returns response status of a HEAD request, e.g. to check that a server
+    // is reachable
+    pub fn head(&self, url: &str) -> Result<StatusCode, Box<dyn Error>> {
+        let status = self.request(Method::HEAD, url)?.send()?.status();
+        Ok(status)
+    }
+
+    // Returns JSON response body and Link header, for paginated APIs
+    #[cfg(not(feature = "git2"))]
+    pub fn get_json_page(&self, url: &str) -> Result<(String, Option<String>), Box<dyn Error>> {
+        let response = self
+            .get(url)?
+            .header("Accept", "application/json")
+            .send()?
+            .error_for_status()?;
+        let link = response
+            .headers()
+            .get("Link")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let text = response.text()?;
+        Ok((text, link))
+    }
 }
 
+const MAX_REDIRECTS: usize = 10;
+
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
 const HTTP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
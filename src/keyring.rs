@@ -0,0 +1,628 @@
+use std::{
+    error::Error,
+    fs::{create_dir_all, read, read_dir, remove_file, set_permissions, File},
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use pgp::{
+    composed::SignedPublicSubKey, packet::SignatureType, types::KeyTrait, Deserializable,
+    SignedPublicKey, StandaloneSignature,
+};
+
+use crate::{
+    shared::{Binary, DotDir},
+    utils::{verify_detached_pgp_signature, wprintln, write_file_atomically, NEWLINE},
+};
+
+// HashiCorp's current public key is embedded, pinned by fingerprint, see
+// https://www.hashicorp.com/trust/security. The 2014 key (348FFC4C) was
+// retired in 2021, and is not trusted.
+const HASHICORP_PGP_PUBLIC_KEYS: [(&str, &str); 1] = [(
+    "C874011F0AB405110D02105534365D9472D7468F",
+    include_str!("keys/hashicorp-72D7468F.asc"),
+)];
+
+// Public keys for verifying release signatures: built-in keys, a legacy
+// etc/<binary>.asc file, and read-only *.asc files in etc/keys/<keyring>
+#[derive(Clone, Copy)]
+pub enum Keyring {
+    HashiCorp,
+    OpenTofu,
+}
+
+impl Keyring {
+    pub fn for_binary(binary: &Binary) -> Result<Keyring, Box<dyn Error>> {
+        match binary {
+            Binary::Terraform | Binary::HashiCorp(_) => Ok(Keyring::HashiCorp),
+            Binary::OpenTofu => Ok(Keyring::OpenTofu),
+            other => Err(format!("{} downloads are not PGP signed", other).into()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Keyring::HashiCorp => "hashicorp",
+            Keyring::OpenTofu => "opentofu",
+        }
+    }
+
+    fn built_in_keys(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Keyring::HashiCorp => &HASHICORP_PGP_PUBLIC_KEYS,
+            Keyring::OpenTofu => &[],
+        }
+    }
+
+    fn legacy_key_file_name(&self) -> &'static str {
+        match self {
+            Keyring::HashiCorp => "terraform.asc",
+            Keyring::OpenTofu => "opentofu.asc",
+        }
+    }
+
+    fn get_dir(&self, dot_dir: &DotDir) -> PathBuf {
+        dot_dir.etc.join(KEYS_DIR_NAME).join(self.name())
+    }
+}
+
+struct KeyringEntry {
+    key: SignedPublicKey,
+    // None for built-in keys
+    path: Option<PathBuf>,
+}
+
+// Keys for verifying signatures, revoked and expired keys are skipped with a
+// warning
+pub fn load_keyring(
+    dot_dir: &DotDir,
+    keyring: Keyring,
+) -> Result<Vec<SignedPublicKey>, Box<dyn Error>> {
+    let keys = load_keyring_entries(dot_dir, keyring)?
+        .into_iter()
+        .map(|e| e.key)
+        .filter(|key| match check_key_usable(key) {
+            Ok(()) => {
+                warn_if_expiring(key);
+                true
+            }
+            Err(e) => {
+                wprintln(&format!("{}, ignoring it", e));
+                false
+            }
+        })
+        .collect();
+    Ok(keys)
+}
+
+// Keyring problems, for diagnostics
+pub struct KeyringStatus {
+    pub key_count: usize,
+    // Key ID and expiry date of expired keys
+    pub expired_keys: Vec<(String, String)>,
+    // Key IDs of revoked keys
+    pub revoked_keys: Vec<String>,
+    // Key files that are ignored, because they are not read-only
+    pub writable_key_files: Vec<PathBuf>,
+}
+
+// Like load_keyring, but reports ignored key files instead of warning
+pub fn get_keyring_status(
+    dot_dir: &DotDir,
+    keyring: Keyring,
+) -> Result<KeyringStatus, Box<dyn Error>> {
+    let mut keys = Vec::new();
+    for (fingerprint, armored_key) in keyring.built_in_keys() {
+        keys.push(load_pinned_key(fingerprint, armored_key)?);
+    }
+    let mut writable_key_files = Vec::new();
+    for key_path in get_key_file_paths(dot_dir, keyring)? {
+        if !key_path.metadata()?.permissions().readonly() {
+            writable_key_files.push(key_path);
+        } else if let Some(key) = load_key_file(&key_path)? {
+            if keys.iter().all(|k| k.fingerprint() != key.fingerprint()) {
+                keys.push(key);
+            }
+        }
+    }
+    let expired_keys = keys
+        .iter()
+        .filter_map(|k| Some((k, k.expires_at()?)))
+        .filter(|(_, t)| *t <= Utc::now())
+        .map(|(k, t)| (get_key_id(k), format_date(&t)))
+        .collect();
+    let revoked_keys = keys
+        .iter()
+        .filter(|k| is_revoked(k))
+        .map(get_key_id)
+        .collect();
+    Ok(KeyringStatus {
+        key_count: keys.len(),
+        expired_keys,
+        revoked_keys,
+        writable_key_files,
+    })
+}
+
+pub fn add_key(binary: Binary, dot_dir: DotDir, key_path: &Path) -> Result<String, Box<dyn Error>> {
+    let keyring = Keyring::for_binary(&binary)?;
+    let key = parse_key(&read(key_path)?)
+        .map_err(|e| format!("Invalid PGP key {}: {}", key_path.display(), e))?;
+    key.verify()
+        .map_err(|e| format!("Invalid PGP key {}: {}", key_path.display(), e))?;
+    check_key_usable(&key)?;
+    if find_key(dot_dir.clone(), keyring, &get_fingerprint(&key)).is_ok() {
+        return Err(format!(
+            "Key {} is already in {} keyring",
+            get_key_id(&key),
+            keyring.name()
+        )
+        .into());
+    }
+    let keyring_dir = keyring.get_dir(&dot_dir);
+    create_dir_all(&keyring_dir)?;
+    let dest_path = keyring_dir.join(format!("{}.asc", get_fingerprint(&key)));
+    let armored_key = key.to_armored_string(None)?;
+    write_file_atomically(&dest_path, &mut armored_key.as_bytes(), false)?;
+    let mut permissions = dest_path.metadata()?.permissions();
+    permissions.set_readonly(true);
+    set_permissions(&dest_path, permissions)?;
+    warn_if_expiring(&key);
+    Ok(format!(
+        "Added key {} ({}) to {} keyring",
+        get_key_id(&key),
+        get_user_id(&key),
+        keyring.name()
+    ))
+}
+
+pub fn list_keys(binary: Option<Binary>, dot_dir: DotDir) -> Result<String, Box<dyn Error>> {
+    let keyrings = match binary {
+        Some(binary) => vec![Keyring::for_binary(&binary)?],
+        None => vec![Keyring::HashiCorp, Keyring::OpenTofu],
+    };
+    let mut lines = Vec::new();
+    for keyring in keyrings {
+        for entry in load_keyring_entries(&dot_dir, keyring)? {
+            warn_if_expiring(&entry.key);
+            lines.push(format!(
+                "{} {} {} ({}, {})",
+                keyring.name(),
+                get_key_id(&entry.key),
+                get_user_id(&entry.key),
+                describe_source(&entry),
+                describe_expiry(entry.key.expires_at())
+            ));
+        }
+    }
+    Ok(lines.join(NEWLINE))
+}
+
+pub fn remove_key(binary: Binary, dot_dir: DotDir, key_id: &str) -> Result<String, Box<dyn Error>> {
+    let keyring = Keyring::for_binary(&binary)?;
+    let entry = find_key(dot_dir, keyring, key_id)?;
+    let key_path = entry.path.ok_or_else(|| {
+        format!(
+            "Key {} is built in, it cannot be removed",
+            get_key_id(&entry.key)
+        )
+    })?;
+    // Read-only files cannot be removed on Windows
+    #[cfg(windows)]
+    {
+        let mut permissions = key_path.metadata()?.permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        set_permissions(&key_path, permissions)?;
+    }
+    remove_file(&key_path)?;
+    Ok(format!(
+        "Removed key {} from {} keyring",
+        get_key_id(&entry.key),
+        keyring.name()
+    ))
+}
+
+pub fn show_key(binary: Binary, dot_dir: DotDir, key_id: &str) -> Result<String, Box<dyn Error>> {
+    let keyring = Keyring::for_binary(&binary)?;
+    let entry = find_key(dot_dir, keyring, key_id)?;
+    let key = &entry.key;
+    warn_if_expiring(key);
+    let mut lines = vec![
+        format!("Key ID:      {}", get_key_id(key)),
+        format!("Fingerprint: {}", get_fingerprint(key)),
+        format!("Source:      {}", describe_source(&entry)),
+    ];
+    for user in &key.details.users {
+        lines.push(format!("User ID:     {}", user.id.id()));
+    }
+    lines.push(format!(
+        "Created:     {}",
+        format_date(key.primary_key.created_at())
+    ));
+    lines.push(format!(
+        "Expiry:      {}",
+        describe_expiry(key.expires_at())
+    ));
+    let can_sign = key
+        .details
+        .users
+        .iter()
+        .flat_map(|u| &u.signatures)
+        .any(|s| s.key_flags().sign());
+    lines.push(format!(
+        "Signing:     {}",
+        if can_sign { "yes" } else { "no" }
+    ));
+    for subkey in &key.public_subkeys {
+        let expires_at = subkey
+            .signatures
+            .iter()
+            .filter_map(|s| s.key_expiration_time())
+            .max()
+            .map(|t| *subkey.key.created_at() + Duration::seconds(t.timestamp()));
+        let can_sign = subkey.signatures.iter().any(|s| s.key_flags().sign());
+        lines.push(format!(
+            "Subkey:      {} ({}, {})",
+            &hex::encode_upper(subkey.key.fingerprint())[32..],
+            if can_sign { "signing" } else { "not signing" },
+            describe_expiry(expires_at)
+        ));
+    }
+    Ok(lines.join(NEWLINE))
+}
+
+// Last 8 hex digits of the fingerprint, e.g. 72D7468F
+pub fn get_key_id(key: &SignedPublicKey) -> String {
+    let fingerprint = get_fingerprint(key);
+    fingerprint[fingerprint.len() - 8..].to_string()
+}
+
+pub fn get_fingerprint(key: &SignedPublicKey) -> String {
+    hex::encode(key.fingerprint()).to_uppercase()
+}
+
+fn load_keyring_entries(
+    dot_dir: &DotDir,
+    keyring: Keyring,
+) -> Result<Vec<KeyringEntry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    for (fingerprint, armored_key) in keyring.built_in_keys() {
+        entries.push(KeyringEntry {
+            key: load_pinned_key(fingerprint, armored_key)?,
+            path: None,
+        });
+    }
+    for key_path in get_key_file_paths(dot_dir, keyring)? {
+        if let Some(key) = load_key_file(&key_path)? {
+            entries.push(KeyringEntry {
+                key,
+                path: Some(key_path),
+            });
+        }
+    }
+    let mut fingerprints = Vec::new();
+    entries.retain(|e| {
+        let fingerprint = e.key.fingerprint();
+        let is_new = !fingerprints.contains(&fingerprint);
+        fingerprints.push(fingerprint);
+        is_new
+    });
+    Ok(entries)
+}
+
+// Legacy etc/<binary>.asc file (if any), then *.asc files in etc/keys/<keyring>
+fn get_key_file_paths(dot_dir: &DotDir, keyring: Keyring) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut key_paths = vec![dot_dir.etc.join(keyring.legacy_key_file_name())];
+    let keyring_dir = keyring.get_dir(dot_dir);
+    if keyring_dir.is_dir() {
+        let mut dir_key_paths: Vec<_> = read_dir(&keyring_dir)?
+            .filter_map(|r| Some(r.ok()?.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "asc"))
+            .collect();
+        dir_key_paths.sort();
+        key_paths.extend(dir_key_paths);
+    }
+    key_paths.retain(|p| p.is_file());
+    Ok(key_paths)
+}
+
+// Key ID or fingerprint, case insensitive
+fn find_key(
+    dot_dir: DotDir,
+    keyring: Keyring,
+    key_id: &str,
+) -> Result<KeyringEntry, Box<dyn Error>> {
+    let key_id = key_id.replace(' ', "").to_uppercase();
+    let entry = load_keyring_entries(&dot_dir, keyring)?
+        .into_iter()
+        .find(|e| key_id.len() >= 8 && get_fingerprint(&e.key).ends_with(&key_id));
+    entry.ok_or_else(|| format!("Key {} not found in {} keyring", key_id, keyring.name()).into())
+}
+
+fn load_pinned_key(
+    fingerprint: &str,
+    armored_key: &str,
+) -> Result<SignedPublicKey, Box<dyn Error>> {
+    let (key, _) = SignedPublicKey::from_armor_single(Cursor::new(armored_key))?;
+    if get_fingerprint(&key) != fingerprint {
+        return Err(format!(
+            "Embedded PGP key fingerprint mismatch, expected {}",
+            fingerprint
+        )
+        .into());
+    }
+    Ok(key)
+}
+
+// Key files must be read-only, others are skipped with a warning
+fn load_key_file(key_path: &Path) -> Result<Option<SignedPublicKey>, Box<dyn Error>> {
+    if !key_path.metadata()?.permissions().readonly() {
+        wprintln(&format!(
+            "Ignoring PGP key {}, it must be read-only",
+            key_path.display()
+        ));
+        return Ok(None);
+    }
+    let (key, _) = SignedPublicKey::from_armor_single(File::open(key_path)?)
+        .map_err(|e| format!("Invalid PGP key {}: {}", key_path.display(), e))?;
+    Ok(Some(key))
+}
+
+// Armored or binary key
+fn parse_key(bytes: &[u8]) -> Result<SignedPublicKey, Box<dyn Error>> {
+    let key = if bytes.starts_with(b"-----BEGIN") {
+        SignedPublicKey::from_armor_single(Cursor::new(bytes))?.0
+    } else {
+        SignedPublicKey::from_bytes(bytes)?
+    };
+    Ok(key)
+}
+
+fn get_user_id(key: &SignedPublicKey) -> String {
+    key.details
+        .users
+        .first()
+        .map(|u| u.id.id().to_string())
+        .unwrap_or_default()
+}
+
+fn describe_source(entry: &KeyringEntry) -> String {
+    match &entry.path {
+        Some(path) => path.display().to_string(),
+        None => "built-in".to_string(),
+    }
+}
+
+fn describe_expiry(expires_at: Option<DateTime<Utc>>) -> String {
+    match expires_at {
+        Some(t) if t <= Utc::now() => format!("expired {}", format_date(&t)),
+        Some(t) => format!("expires {}", format_date(&t)),
+        None => "no expiry".to_string(),
+    }
+}
+
+fn warn_if_expiring(key: &SignedPublicKey) {
+    let expires_at = match key.expires_at() {
+        Some(t) => t,
+        None => return,
+    };
+    if expires_at <= Utc::now() {
+        wprintln(&format!(
+            "Key {} expired on {}",
+            get_key_id(key),
+            format_date(&expires_at)
+        ));
+    } else if expires_at <= Utc::now() + Duration::days(KEY_EXPIRY_WARNING_DAYS) {
+        wprintln(&format!(
+            "Key {} expires on {}",
+            get_key_id(key),
+            format_date(&expires_at)
+        ));
+    }
+}
+
+fn check_key_usable(key: &SignedPublicKey) -> Result<(), Box<dyn Error>> {
+    if is_revoked(key) {
+        return Err(format!("Key {} has been revoked", get_key_id(key)).into());
+    }
+    match key.expires_at() {
+        Some(t) if t <= Utc::now() => {
+            Err(format!("Key {} expired on {}", get_key_id(key), format_date(&t)).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+// Verifies a detached signature by given key of the keyring. Subkeys expire
+// and are revoked separately from the primary key, so when a subkey made the
+// signature, it must be usable too.
+pub fn verify_signature(
+    content: &[u8],
+    signature: &StandaloneSignature,
+    public_key: &SignedPublicKey,
+) -> Result<(), Box<dyn Error>> {
+    verify_detached_pgp_signature(content, signature, public_key)?;
+    if public_key.is_signing_key() && signature.verify(public_key, content).is_ok() {
+        return Ok(());
+    }
+    match public_key
+        .public_subkeys
+        .iter()
+        .find(|sub_key| sub_key.is_signing_key() && signature.verify(*sub_key, content).is_ok())
+    {
+        Some(sub_key) => check_subkey_usable(sub_key),
+        None => Ok(()),
+    }
+}
+
+fn check_subkey_usable(sub_key: &SignedPublicSubKey) -> Result<(), Box<dyn Error>> {
+    let fingerprint = hex::encode(sub_key.fingerprint()).to_uppercase();
+    let key_id = &fingerprint[fingerprint.len() - 8..];
+    if sub_key
+        .signatures
+        .iter()
+        .any(|s| s.typ() == SignatureType::SubkeyRevocation)
+    {
+        return Err(format!("Signing subkey {} has been revoked", key_id).into());
+    }
+    // Expiration is an offset from key creation, in the latest binding signature
+    let expires_at = sub_key
+        .signatures
+        .iter()
+        .filter(|s| s.typ() == SignatureType::SubkeyBinding)
+        .max_by_key(|s| s.created().copied())
+        .and_then(|s| s.key_expiration_time())
+        .map(|t| *sub_key.key.created_at() + Duration::seconds(t.timestamp()));
+    match expires_at {
+        Some(t) if t <= Utc::now() => {
+            Err(format!("Signing subkey {} expired on {}", key_id, format_date(&t)).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn is_revoked(key: &SignedPublicKey) -> bool {
+    !key.details.revocation_signatures.is_empty()
+}
+
+fn format_date(t: &DateTime<Utc>) -> String {
+    t.format("%Y-%m-%d").to_string()
+}
+
+const KEYS_DIR_NAME: &str = "keys";
+
+const KEY_EXPIRY_WARNING_DAYS: i64 = 30;
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_load_keyring() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let keyring = load_keyring(&dot_dir, Keyring::HashiCorp).unwrap();
+        let key_ids: Vec<String> = keyring.iter().map(get_key_id).collect();
+        assert_eq!(key_ids, vec!["72D7468F"]);
+        assert!(load_keyring(&dot_dir, Keyring::OpenTofu)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_keyring_status() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let status = get_keyring_status(&dot_dir, Keyring::HashiCorp).unwrap();
+        assert_eq!(status.key_count, 1);
+        assert!(status.expired_keys.is_empty());
+        assert!(status.revoked_keys.is_empty());
+        assert!(status.writable_key_files.is_empty());
+        let legacy_key_path = dot_dir.etc.join("opentofu.asc");
+        std::fs::copy("tests/hashicorp-72D7468F-refreshed.asc", &legacy_key_path).unwrap();
+        let status = get_keyring_status(&dot_dir, Keyring::OpenTofu).unwrap();
+        assert_eq!(status.key_count, 0);
+        assert_eq!(status.writable_key_files, vec![legacy_key_path]);
+    }
+
+    #[test]
+    fn test_expired_and_revoked_keys_are_not_used() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        for key_file_name in ["hashicorp-72D7468F.asc", "revoked-48AABEE2.asc"] {
+            let key_path = Path::new("tests").join(key_file_name);
+            assert!(add_key(Binary::OpenTofu, dot_dir.clone(), &key_path).is_err());
+            let legacy_key_path = dot_dir.etc.join("opentofu.asc");
+            std::fs::copy(&key_path, &legacy_key_path).unwrap();
+            let mut permissions = legacy_key_path.metadata().unwrap().permissions();
+            permissions.set_readonly(true);
+            set_permissions(&legacy_key_path, permissions).unwrap();
+            assert!(load_keyring(&dot_dir, Keyring::OpenTofu)
+                .unwrap()
+                .is_empty());
+            let status = get_keyring_status(&dot_dir, Keyring::OpenTofu).unwrap();
+            assert_eq!(status.key_count, 1);
+            assert_eq!(
+                status.expired_keys.len() + status.revoked_keys.len(),
+                1,
+                "{}",
+                key_file_name
+            );
+            let mut permissions = legacy_key_path.metadata().unwrap().permissions();
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            set_permissions(&legacy_key_path, permissions).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_add_show_remove_key() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let key_path = Path::new("tests/hashicorp-72D7468F-refreshed.asc");
+        assert!(add_key(Binary::Terraform, dot_dir.clone(), key_path).is_err());
+        assert!(add_key(Binary::Terragrunt, dot_dir.clone(), key_path).is_err());
+        assert_eq!(
+            add_key(Binary::OpenTofu, dot_dir.clone(), key_path).unwrap(),
+            "Added key 72D7468F (HashiCorp Security (hashicorp.com/security) <security@hashicorp.com>) to opentofu keyring"
+        );
+        let stored_key_path = dot_dir
+            .etc
+            .join("keys/opentofu/C874011F0AB405110D02105534365D9472D7468F.asc");
+        assert!(stored_key_path.metadata().unwrap().permissions().readonly());
+        assert_eq!(load_keyring(&dot_dir, Keyring::OpenTofu).unwrap().len(), 1);
+        assert!(show_key(Binary::OpenTofu, dot_dir.clone(), "72d7468f")
+            .unwrap()
+            .contains("Fingerprint: C874011F0AB405110D02105534365D9472D7468F"));
+        assert!(list_keys(None, dot_dir.clone())
+            .unwrap()
+            .contains("opentofu 72D7468F"));
+        assert!(remove_key(Binary::Terraform, dot_dir.clone(), "72D7468F").is_err());
+        assert_eq!(
+            remove_key(Binary::OpenTofu, dot_dir.clone(), "72D7468F").unwrap(),
+            "Removed key 72D7468F from opentofu keyring"
+        );
+        assert!(!stored_key_path.exists());
+    }
+
+    #[test]
+    fn test_embedded_keys_verify_signatures() {
+        let shasums = read("tests/terraform_0.13.1_SHA256SUMS").unwrap();
+        for (fingerprint, armored_key) in HASHICORP_PGP_PUBLIC_KEYS {
+            let key = load_pinned_key(fingerprint, armored_key).unwrap();
+            let signature = StandaloneSignature::from_bytes(
+                File::open(format!(
+                    "tests/terraform_0.13.1_SHA256SUMS.{}.sig",
+                    get_key_id(&key)
+                ))
+                .unwrap(),
+            )
+            .unwrap();
+            // Made by signing subkey 7685B676, which has expired since
+            verify_detached_pgp_signature(&shasums, &signature, &key).unwrap();
+            assert_eq!(
+                verify_signature(&shasums, &signature, &key)
+                    .unwrap_err()
+                    .to_string(),
+                "Signing subkey 7685B676 expired on 2022-04-20"
+            );
+        }
+        assert!(load_pinned_key("0".repeat(40).as_str(), HASHICORP_PGP_PUBLIC_KEYS[0].1).is_err());
+    }
+
+    #[test]
+    fn test_describe_expiry() {
+        assert_eq!(describe_expiry(None), "no expiry");
+        let t = DateTime::parse_from_rfc3339("2026-04-18T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(describe_expiry(Some(t)), "expired 2026-04-18");
+        let t = Utc::now() + Duration::days(365);
+        assert!(describe_expiry(Some(t)).starts_with("expires "));
+    }
+}
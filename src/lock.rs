@@ -0,0 +1,151 @@
+use std::{
+    env::var,
+    error::Error,
+    fs::{create_dir_all, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    process,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use fs2::FileExt;
+
+use crate::{
+    shared::{Binary, DotDir},
+    utils::wprintln,
+};
+
+// Advisory file lock, released when dropped (or when the process exits).
+// Lock files live in var/<binary>/locks and contain the PID of the holder.
+pub struct Lock {
+    file: File,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+// Lock for installing (or removing) given version of binary
+pub fn lock_version(
+    binary: &Binary,
+    version: &str,
+    dot_dir: &DotDir,
+) -> Result<Lock, Box<dyn Error>> {
+    let description = format!("{} {}", binary, version);
+    lock(
+        binary,
+        &format!("install-{}", version),
+        &description,
+        dot_dir,
+    )
+}
+
+// Lock for changing the selected version of binary (bin/ and var/ files)
+pub fn lock_binary(binary: &Binary, dot_dir: &DotDir) -> Result<Lock, Box<dyn Error>> {
+    lock(binary, "select", &binary.to_string(), dot_dir)
+}
+
+fn lock(
+    binary: &Binary,
+    name: &str,
+    description: &str,
+    dot_dir: &DotDir,
+) -> Result<Lock, Box<dyn Error>> {
+    let lock_dir = dot_dir.var.join(binary).join("locks");
+    create_dir_all(&lock_dir)?;
+    let lock_file_path = lock_dir.join(format!("{}.lock", name));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_file_path)?;
+    let timeout = get_lock_timeout()?;
+    let start = Instant::now();
+    let mut waiting = false;
+    while file.try_lock_exclusive().is_err() {
+        let holder = read_holder_pid(&mut file);
+        if start.elapsed() >= timeout {
+            return Err(format!(
+                "Timed out after {}s waiting for lock on {} held by {} ({})",
+                timeout.as_secs(),
+                description,
+                holder,
+                lock_file_path.display()
+            )
+            .into());
+        }
+        if !waiting {
+            wprintln(&format!(
+                "Waiting for lock on {} held by {}",
+                description, holder
+            ));
+            waiting = true;
+        }
+        sleep(LOCK_RETRY_INTERVAL);
+    }
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    write!(file, "{}", process::id())?;
+    file.flush()?;
+    Ok(Lock { file })
+}
+
+fn read_holder_pid(file: &mut File) -> String {
+    let mut pid = String::new();
+    match file
+        .seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_to_string(&mut pid))
+    {
+        Ok(_) if !pid.trim().is_empty() => format!("process {}", pid.trim()),
+        _ => "another process".to_string(),
+    }
+}
+
+// Wait timeout in seconds can be set with environment variable TERVE_LOCK_TIMEOUT
+fn get_lock_timeout() -> Result<Duration, Box<dyn Error>> {
+    match var("TERVE_LOCK_TIMEOUT") {
+        Ok(s) => {
+            let secs: u64 = s
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid TERVE_LOCK_TIMEOUT '{}', expected seconds", s))?;
+            Ok(Duration::from_secs(secs))
+        }
+        Err(_) => Ok(DEFAULT_LOCK_TIMEOUT),
+    }
+}
+
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(300);
+
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_lock_version() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let lock = lock_version(&Binary::Terraform, "1.5.7", &dot_dir).unwrap();
+        let lock_file_path = dot_dir
+            .var
+            .join(Binary::Terraform)
+            .join("locks")
+            .join("install-1.5.7.lock");
+        let mut other = File::open(&lock_file_path).unwrap();
+        assert!(other.try_lock_exclusive().is_err());
+        assert_eq!(
+            read_holder_pid(&mut other),
+            format!("process {}", process::id())
+        );
+        drop(lock);
+        assert!(other.try_lock_exclusive().is_ok());
+    }
+}
@@ -1,23 +1,54 @@
 use dirs::home_dir;
+use exec::ExecOptions;
 use pico_args::Arguments;
 use semver::Version;
-use shared::{Action, Binary, DotDir};
+use shared::{get_os_arch, Action, Binary, DotDir, InstallOptions};
 use std::{
-    env::consts::{ARCH, OS},
+    env::{args_os, current_dir},
+    ffi::OsString,
+    path::PathBuf,
     str::FromStr,
 };
 use std::{error::Error, process};
-use terraform::TF_GIT_REPOSITORY_URL;
-use terragrunt::TG_GIT_REPOSITORY_URL;
 use utils::{eprintln, println};
+use version::{get_wanted_version, resolve_version, resolve_wanted_version};
 
+mod archive;
+mod bundle;
+mod cache;
+mod config;
+mod constraint;
+mod doctor;
+mod exec;
+#[cfg(not(feature = "git2"))]
+mod github;
+mod hashicorp;
+mod hcl;
 mod http;
+mod keyring;
+mod lock;
+mod manifest;
+mod opentofu;
 mod shared;
+mod shim;
 mod terraform;
 mod terragrunt;
+mod tool;
 mod utils;
+mod version;
+mod version_check;
 
 fn main() {
+    if let Some(binary) = shim::get_shim_binary() {
+        process::exit(match shim::run_shim(binary) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln(e);
+                1
+            }
+        });
+    }
+
     process::exit(match run() {
         Ok(s) => {
             if !s.is_empty() {
@@ -33,7 +64,12 @@ fn main() {
 }
 
 fn run() -> Result<String, Box<dyn Error>> {
-    let mut args = Arguments::from_env();
+    let mut args: Vec<OsString> = args_os().skip(1).collect();
+    let exec_args = match args.iter().position(|a| a == "--") {
+        Some(i) => args.split_off(i).into_iter().skip(1).collect(),
+        None => Vec::new(),
+    };
+    let mut args = Arguments::from_vec(args);
 
     if args.contains(["-h", "--help"]) {
         return Ok(USAGE_HELP_MSG.to_string());
@@ -50,21 +86,155 @@ fn run() -> Result<String, Box<dyn Error>> {
             return Ok(format!("Created {}", dot_dir.root.display()));
         }
 
-        let (action, binary, version, os, arch) = get_params(args)?;
+        // Terraform version for exec, or versions for bundle export
+        let tf_option: Option<String> = args.opt_value_from_str("--tf")?;
 
-        match (action, binary, version) {
-            (Action::List, binary, None) => shared::list_installed_versions(binary, dot_dir),
-            (Action::List, Binary::Terraform, Some(v)) if v.is_remote() => {
-                shared::list_available_versions(TF_GIT_REPOSITORY_URL)
+        let exec_options = ExecOptions {
+            install: args.contains("--install"),
+            tf_version: tf_option.clone(),
+            args: exec_args,
+        };
+
+        let install_options = InstallOptions {
+            offline: args.contains("--offline"),
+            skip_signature: args.contains("--insecure-skip-signature"),
+            ..Default::default()
+        };
+
+        let action = get_action(&mut args)?;
+
+        if let Action::Cache = action {
+            return match args.subcommand()?.as_deref() {
+                Some("l" | "list") => cache::list_cached_files(dot_dir),
+                Some("size") => cache::get_cache_size(dot_dir),
+                Some("clean") => cache::clean_cache(dot_dir),
+                _ => Err(INVALID_ARGS_MSG.into()),
+            };
+        }
+
+        if let Action::Verify = action {
+            let binary = match args.subcommand()? {
+                Some(name) => Some(Binary::lookup(&name, &dot_dir)?),
+                None => None,
+            };
+            let version: Option<String> = args.subcommand()?;
+            if binary.is_none() && version.is_some() {
+                return Err(INVALID_ARGS_MSG.into());
+            }
+            return manifest::verify_installed_versions(binary, version, dot_dir);
+        }
+
+        if let Action::Doctor = action {
+            return doctor::run_diagnostics(dot_dir, &install_options);
+        }
+
+        if let Action::Key = action {
+            return match args.subcommand()?.as_deref() {
+                Some("add") => {
+                    let binary = get_key_binary(&mut args, &dot_dir)?;
+                    let key_path: PathBuf = args.free_from_str()?;
+                    keyring::add_key(binary, dot_dir, &key_path)
+                }
+                Some("l" | "list") => {
+                    let binary = match args.subcommand()? {
+                        Some(name) => Some(Binary::lookup(&name, &dot_dir)?),
+                        None => None,
+                    };
+                    keyring::list_keys(binary, dot_dir)
+                }
+                Some("remove") => {
+                    let binary = get_key_binary(&mut args, &dot_dir)?;
+                    let key_id: String = args.free_from_str()?;
+                    keyring::remove_key(binary, dot_dir, &key_id)
+                }
+                Some("show") => {
+                    let binary = get_key_binary(&mut args, &dot_dir)?;
+                    let key_id: String = args.free_from_str()?;
+                    keyring::show_key(binary, dot_dir, &key_id)
+                }
+                _ => Err(INVALID_ARGS_MSG.into()),
+            };
+        }
+
+        if let Action::Bundle = action {
+            let (os, arch) = get_os_arch();
+            return match args.subcommand()?.as_deref() {
+                Some("export") => {
+                    let mut binaries = Vec::new();
+                    let tg_option = args.opt_value_from_str("--tg")?;
+                    let tofu_option = args.opt_value_from_str("--tofu")?;
+                    for (name, versions) in
+                        [("tf", tf_option), ("tg", tg_option), ("tofu", tofu_option)]
+                    {
+                        if let Some(versions) = versions {
+                            binaries.push(get_bundle_binary(name, &versions, &dot_dir)?);
+                        }
+                    }
+                    for option in args.values_from_str::<_, String>("--bin")? {
+                        let (name, versions) = option
+                            .split_once('=')
+                            .ok_or("Option --bin must be given as <binary>=<versions>")?;
+                        binaries.push(get_bundle_binary(name, versions, &dot_dir)?);
+                    }
+                    let os = args.opt_value_from_str("--os")?.unwrap_or(os);
+                    let arch = args.opt_value_from_str("--arch")?.unwrap_or(arch);
+                    let bundle_path: PathBuf = args.free_from_str()?;
+                    bundle::export_bundle(binaries, dot_dir, os, arch, &bundle_path)
+                }
+                Some("import") => {
+                    let bundle_path: PathBuf = args.free_from_str()?;
+                    bundle::import_bundle(&bundle_path, dot_dir, os, arch)
+                }
+                _ => Err(INVALID_ARGS_MSG.into()),
+            };
+        }
+
+        let (binary, version, os, arch) = get_params(args, &dot_dir)?;
+
+        // Exec keeps stdout clean for the executed binary
+        let quiet = matches!(action, Action::Exec);
+
+        let version = match (&action, version) {
+            (Action::Install | Action::Select | Action::Use | Action::Exec, None) => {
+                let (version, source) = resolve_wanted_version(&binary)?;
+                if !quiet {
+                    println(&format!("Using {} {} from {}", binary, version, source));
+                }
+                Some(version)
             }
-            (Action::List, Binary::Terragrunt, Some(v)) if v.is_remote() => {
-                shared::list_available_versions(TG_GIT_REPOSITORY_URL)
+            (_, v) => v,
+        };
+
+        let version = match (&action, version) {
+            (Action::Install | Action::Select | Action::Use | Action::Exec, Some(v))
+                if v.is_version_spec() =>
+            {
+                let versions = match action {
+                    Action::Install | Action::Use => {
+                        get_available_versions(&binary, &dot_dir, &os, &arch, &install_options)?
+                    }
+                    Action::Exec if exec_options.install => {
+                        get_available_versions(&binary, &dot_dir, &os, &arch, &install_options)?
+                    }
+                    _ => shared::get_installed_versions(&binary, &dot_dir)?,
+                };
+                let resolved = resolve_version(&binary, &v, &versions)?;
+                if !quiet {
+                    println(&format!("Resolved {} '{}' to {}", binary, v, resolved));
+                }
+                Some(resolved)
             }
-            (Action::Install, Binary::Terraform, Some(v)) if v.is_semver() => {
-                terraform::install_binary_version(v, dot_dir, os, arch)
+            (_, v) => v,
+        };
+
+        match (action, binary, version) {
+            (Action::List, binary, None) => shared::list_installed_versions(binary, dot_dir),
+            (Action::List, binary, Some(v)) if v.is_remote() => {
+                check_online(&install_options)?;
+                shared::list_available_versions(&binary, &dot_dir, &os, &arch)
             }
-            (Action::Install, Binary::Terragrunt, Some(v)) if v.is_semver() => {
-                terragrunt::install_binary_version(v, dot_dir, os, arch)
+            (Action::Install, binary, Some(v)) if v.is_semver() => {
+                shared::install_binary_version(binary, v, dot_dir, os, arch, &install_options)
             }
             (Action::Select, binary, Some(v)) if v.is_semver() => {
                 shared::select_binary_version(binary, v, dot_dir)
@@ -72,7 +242,27 @@ fn run() -> Result<String, Box<dyn Error>> {
             (Action::Remove, binary, Some(v)) if v.is_semver() => {
                 shared::remove_binary_version(binary, v, dot_dir)
             }
+            (Action::Use, binary, Some(v)) if v.is_semver() => {
+                shared::use_binary_version(binary, v, dot_dir, os, arch, &install_options)
+            }
+            (Action::Exec, binary, Some(v)) if v.is_semver() => exec::exec_binary_version(
+                binary,
+                v,
+                dot_dir,
+                os,
+                arch,
+                exec_options,
+                &install_options,
+            ),
             (Action::Which, binary, None) => shared::get_selected_version(binary, dot_dir),
+            (Action::Shim, binary, None) => shim::enable_shim(binary, dot_dir),
+            (Action::Shim, binary, Some(v)) if v == "off" => shim::disable_shim(binary, dot_dir),
+            (Action::Resolve, binary, None) => {
+                match get_wanted_version(&binary, &current_dir()?)? {
+                    Some((version, source)) => Ok(format!("{} (set by {})", version, source)),
+                    None => Ok("".to_string()),
+                }
+            }
             _ => Err(INVALID_ARGS_MSG.into()),
         }
     } else {
@@ -80,40 +270,75 @@ fn run() -> Result<String, Box<dyn Error>> {
     }
 }
 
-type Params = (Action, Binary, Option<String>, String, String);
+fn get_available_versions(
+    binary: &Binary,
+    dot_dir: &DotDir,
+    os: &str,
+    arch: &str,
+    install_options: &InstallOptions,
+) -> Result<Vec<Version>, Box<dyn Error>> {
+    check_online(install_options)?;
+    shared::get_available_versions(binary, dot_dir, os, arch)
+}
 
-fn get_params(mut args: Arguments) -> Result<Params, Box<dyn Error>> {
-    let action: Action = match args.subcommand()? {
-        Some(s) => Action::from_str(&s)?,
-        None => return Err(INVALID_ARGS_MSG.into()),
-    };
+fn check_online(install_options: &InstallOptions) -> Result<(), Box<dyn Error>> {
+    if install_options.offline {
+        return Err("Unable to list available versions in offline mode".into());
+    }
+    Ok(())
+}
+
+// Versions are given comma separated, e.g. 1.5.7,1.6.0
+fn get_bundle_binary(
+    name: &str,
+    versions: &str,
+    dot_dir: &DotDir,
+) -> Result<(Binary, Vec<String>), Box<dyn Error>> {
+    let binary = Binary::lookup(name, dot_dir)?;
+    let versions: Vec<String> = versions.split(',').map(|v| v.trim().to_string()).collect();
+    if let Some(v) = versions.iter().find(|v| !v.is_semver()) {
+        return Err(format!(
+            "Invalid {} version '{}', bundles need exact versions",
+            binary, v
+        )
+        .into());
+    }
+    Ok((binary, versions))
+}
+
+fn get_key_binary(args: &mut Arguments, dot_dir: &DotDir) -> Result<Binary, Box<dyn Error>> {
+    match args.subcommand()? {
+        Some(name) => Binary::lookup(&name, dot_dir),
+        None => Err(INVALID_ARGS_MSG.into()),
+    }
+}
 
+fn get_action(args: &mut Arguments) -> Result<Action, Box<dyn Error>> {
+    match args.subcommand()? {
+        Some(s) => Ok(Action::from_str(&s)?),
+        None => Err(INVALID_ARGS_MSG.into()),
+    }
+}
+
+type Params = (Binary, Option<String>, String, String);
+
+fn get_params(mut args: Arguments, dot_dir: &DotDir) -> Result<Params, Box<dyn Error>> {
     let binary: Binary = match args.subcommand()? {
-        Some(s) => Binary::from_str(&s)?,
+        Some(s) => Binary::lookup(&s, dot_dir)?,
         None => return Err(INVALID_ARGS_MSG.into()),
     };
 
     let version: Option<String> = args.subcommand()?;
 
-    let os = match OS {
-        "linux" => "linux",
-        "macos" => "darwin",
-        "windows" => "windows",
-        other => panic!("Unsupported OS: {}", other),
-    };
-
-    let arch = match ARCH {
-        "x86_64" => "amd64",
-        "aarch64" => "arm64",
-        other => panic!("Unsupported architecture: {}", other),
-    };
+    let (os, arch) = get_os_arch();
 
-    Ok((action, binary, version, os.to_string(), arch.to_string()))
+    Ok((binary, version, os, arch))
 }
 
 trait VersionQualifier {
     fn is_remote(&self) -> bool;
     fn is_semver(&self) -> bool;
+    fn is_version_spec(&self) -> bool;
 }
 
 impl VersionQualifier for String {
@@ -124,17 +349,35 @@ impl VersionQualifier for String {
     fn is_semver(&self) -> bool {
         Version::parse(self).is_ok()
     }
+
+    fn is_version_spec(&self) -> bool {
+        !self.is_remote() && !self.is_semver()
+    }
 }
 
 const TERVE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const USAGE_HELP_MSG: &str = "\
-Unified terraform and terragrunt version manager
+Unified terraform, terragrunt and opentofu version manager
 
 See https://github.com/superblk/terve for documentation
 
 USAGE:
   terve <ACTION> <BINARY> [<VERSION>]
+  terve exec <BINARY> [<VERSION>] [--install] [--tf <VERSION>] -- <ARGS>
+  terve cache <list|size|clean>
+  terve bundle export [--tf <VERSIONS>] [--tg <VERSIONS>] [--tofu <VERSIONS>]
+    [--bin <BINARY>=<VERSIONS>] [--os <OS>] [--arch <ARCH>] <FILE>
+  terve bundle import <FILE>
+  terve key <add|remove|show> <BINARY> <FILE|KEY_ID>
+  terve key list [<BINARY>]
+  terve verify [<BINARY> [<VERSION>]]
+  terve doctor [--offline]
+
+  If VERSION is omitted for install, select, use or exec, it is read from
+  environment variable TERVE_<BINARY>_VERSION, or from the nearest
+  .<binary>-version or .tool-versions file (walking up from current
+  directory).
 
 ACTION:
   l, list               Lists versions
@@ -142,27 +385,77 @@ ACTION:
   s, select             Selects installed version
   r, remove             Removes installed version
   w, which              Prints selected version
+  u, use                Installs (if not installed) and selects given version
+  x, exec               Runs given version, without selecting it
+  resolve               Prints wanted version and where it is set
+  shim                  Enables shim mode (or disables it, given 'off')
+  cache                 Lists, sizes or cleans the download cache
+  bundle                Exports or imports a bundle for offline installs
+  key                   Manages PGP public keys for verifying downloads
+  verify                Checks installed binaries against their install records
+  doctor                Diagnoses PATH, selected binaries, keys, filesystem
+                        and network problems
 
 BINARY:
   tf, terraform         Terraform (https://www.terraform.io/)
   tg, terragrunt        Terragrunt (https://terragrunt.gruntwork.io/)
+  tofu, opentofu        OpenTofu (https://opentofu.org/)
+  packer, vault, consul, nomad, terraform-ls
+                        HashiCorp products (https://releases.hashicorp.com/)
+  <tool>                Tool described in ~/.terve/etc/tools.d/<tool>.toml
 
 VERSION:
   r, remote             Available (remote) versions
   x.y.z                 Semantic version string, e.g. 0.15.4
+  <constraint>          Version constraint, e.g. '~> 1.5' or '>= 1.3, < 1.6'
+  latest                Latest version (excluding pre-releases)
+  latest:<regex>        Latest version matching regex, e.g. 'latest:^0\\.4'
+  min-required          Oldest version allowed by version constraints in
+                        *.tf files (terraform) and terragrunt.hcl
+  latest-allowed        Latest version allowed by version constraints in
+                        *.tf files (terraform) and terragrunt.hcl
+
+  Install resolves constraints and keywords against available versions,
+  select against installed versions.
 
 FLAGS:
   -h, --help            Prints this help message
   -v, --version         Prints application version
   -b, --bootstrap       Creates ~/.terve directory tree
+  --offline             Installs from the download cache only
+  --insecure-skip-signature
+                        Skips PGP signature verification of HashiCorp
+                        downloads (SHA256 checksums are still verified)
+
+ENVIRONMENT:
+  TERVE_<BINARY>_VERSION
+                        Wanted version, e.g. TERVE_TERRAFORM_VERSION
+  TERVE_LOCK_TIMEOUT    Seconds to wait for concurrent installs (default 300)
+
+EXEC OPTIONS:
+  --install             Installs given version first, if not installed
+  --tf <VERSION>        Terraform version for terragrunt (TERRAGRUNT_TFPATH)
 
 EXAMPLES:
   terve l tf            Lists installed terraform versions
   terve l tf r          Lists available terraform versions
   terve i tf 0.15.4     Installs terraform 0.15.4
   terve s tf 0.15.4     Selects terraform 0.15.4
+  terve i tf '~> 1.5'   Installs latest terraform 1.x (x >= 5)
+  terve i tf latest     Installs latest terraform
   terve r tf 0.15.4     Removes terraform 0.15.4
   terve w tf            Prints selected terraform version
+  terve u tf 0.15.4     Installs and selects terraform 0.15.4
+  terve i tf            Installs terraform version set in .terraform-version
+  terve resolve tf      Prints terraform version set in .terraform-version
+  terve shim tf         Makes ~/.terve/bin/terraform pick version per directory
+  terve cache clean     Removes cached downloads
+  terve bundle export --tf 1.5.7,1.6.0 --tg 0.53.0 out.tar
+                        Exports terraform and terragrunt release files
+  terve bundle import out.tar
+                        Verifies and installs binaries from bundle
+  terve x tf 0.15.4 -- state pull
+                        Runs terraform 0.15.4 with arguments 'state pull'
 ";
 
 const INVALID_ARGS_MSG: &str = "Invalid arguments. Run 'terve --help' for usage";
@@ -0,0 +1,318 @@
+use std::{
+    error::Error,
+    fs::{create_dir_all, read_to_string, remove_file, File},
+    path::{Path, PathBuf},
+};
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    shared::{get_installed_versions, get_selected_version, Binary, DotDir, BINARIES},
+    shim::is_shim_enabled,
+    tool::load_tool_descriptors,
+    utils::{println, sha256_hex, write_file_atomically},
+};
+
+// Integrity record of an installed version, written at install time into
+// var/<binary>/manifests/<version>.toml, e.g.
+//
+//   binary_sha256 = "..."
+//   archive_sha256 = "..."
+//   signature = "verified"
+//   signature_key = "72D7468F"
+#[derive(Deserialize, Serialize)]
+struct InstallManifest {
+    binary_sha256: String,
+    // Downloaded release file (archive, or binary)
+    archive_sha256: String,
+    signature: Signature,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature_key: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Signature {
+    Verified,
+    Skipped,
+    Unsigned,
+}
+
+// Key ID of the PGP key that verified the release signature, or None if
+// verification was skipped
+pub type SignatureKey = Option<String>;
+
+// Written right after the binary is placed in opt/. If the record cannot be
+// written, the binary is removed, so that no version is installed without one.
+pub fn write_install_manifest(
+    dot_dir: &DotDir,
+    opt_file_path: &Path,
+    archive_file: &File,
+    signature: Signature,
+    signature_key: SignatureKey,
+) -> Result<(), Box<dyn Error>> {
+    let result = write_manifest_file(
+        dot_dir,
+        opt_file_path,
+        archive_file,
+        signature,
+        signature_key,
+    );
+    if result.is_err() && opt_file_path.exists() {
+        remove_file(opt_file_path)?;
+    }
+    result
+}
+
+fn write_manifest_file(
+    dot_dir: &DotDir,
+    opt_file_path: &Path,
+    archive_file: &File,
+    signature: Signature,
+    signature_key: SignatureKey,
+) -> Result<(), Box<dyn Error>> {
+    let manifest = InstallManifest {
+        binary_sha256: sha256_hex(&File::open(opt_file_path)?)?,
+        archive_sha256: sha256_hex(archive_file)?,
+        signature,
+        signature_key,
+    };
+    let manifest_path = get_manifest_path(dot_dir, opt_file_path)?;
+    create_dir_all(manifest_path.parent().ok_or("Invalid manifest path")?)?;
+    let manifest_toml = toml::to_string(&manifest)?;
+    write_file_atomically(&manifest_path, &mut manifest_toml.as_bytes(), false)?;
+    Ok(())
+}
+
+pub fn remove_install_manifest(
+    dot_dir: &DotDir,
+    opt_file_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let manifest_path = get_manifest_path(dot_dir, opt_file_path)?;
+    if manifest_path.exists() {
+        remove_file(manifest_path)?;
+    }
+    Ok(())
+}
+
+// Fails if the installed file does not match its install record. Versions
+// installed before records were kept have none, and pass.
+pub fn check_installed_file(dot_dir: &DotDir, opt_file_path: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(manifest) = read_install_manifest(dot_dir, opt_file_path)? {
+        let sha256 = sha256_hex(&File::open(opt_file_path)?)?;
+        if sha256 != manifest.binary_sha256 {
+            return Err(format!(
+                "{} has been modified since install: sha256 is '{}', expected '{}'",
+                opt_file_path.display(),
+                sha256,
+                manifest.binary_sha256
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+// Re-hashes installed versions (and the selected bin/ file) of given binary,
+// or of all binaries, and reports each against its install record
+pub fn verify_installed_versions(
+    binary: Option<Binary>,
+    version: Option<String>,
+    dot_dir: DotDir,
+) -> Result<String, Box<dyn Error>> {
+    let binaries = match binary {
+        Some(binary) => vec![binary],
+        None => BINARIES
+            .iter()
+            .cloned()
+            .chain(
+                load_tool_descriptors(&dot_dir)?
+                    .into_iter()
+                    .map(|t| Binary::Tool(Box::new(t))),
+            )
+            .collect(),
+    };
+    let mut count = 0;
+    let mut failures = 0;
+    for binary in binaries {
+        if !dot_dir.opt.join(&binary).is_dir() {
+            continue;
+        }
+        let versions = match &version {
+            Some(v) => vec![Version::parse(v)?],
+            None => {
+                let mut versions = get_installed_versions(&binary, &dot_dir)?;
+                versions.sort();
+                versions
+            }
+        };
+        let selected_version = get_selected_version(binary.clone(), dot_dir.clone())?;
+        for version in versions {
+            let version = version.to_string();
+            let opt_file_path = dot_dir.opt.join(&binary).join(&version);
+            if !opt_file_path.exists() {
+                return Err(format!("{} version {} is not installed", binary, version).into());
+            }
+            let manifest = read_install_manifest(&dot_dir, &opt_file_path)?;
+            let sha256 = sha256_hex(&File::open(&opt_file_path)?)?;
+            let (status, expected_sha256) = match &manifest {
+                Some(m) if m.binary_sha256 == sha256 => {
+                    (describe_signature(m), m.binary_sha256.clone())
+                }
+                Some(m) => {
+                    failures += 1;
+                    (
+                        format!(
+                            "MODIFIED, sha256 is '{}', expected '{}'",
+                            sha256, m.binary_sha256
+                        ),
+                        m.binary_sha256.clone(),
+                    )
+                }
+                None => ("no install record".to_string(), sha256),
+            };
+            count += 1;
+            println(&format!("{} {}: {}", binary, version, status));
+            if selected_version == version && !is_shim_enabled(&binary, &dot_dir) {
+                let bin_file_path = dot_dir.bin.join(&binary);
+                let bin_sha256 = sha256_hex(&File::open(&bin_file_path)?)?;
+                let status = if bin_sha256 == expected_sha256 {
+                    "OK".to_string()
+                } else {
+                    failures += 1;
+                    format!(
+                        "MODIFIED, sha256 is '{}', expected '{}'",
+                        bin_sha256, expected_sha256
+                    )
+                };
+                count += 1;
+                println(&format!("{}: {}", bin_file_path.display(), status));
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(format!(
+            "{} of {} installed files failed verification",
+            failures, count
+        )
+        .into());
+    }
+    Ok(format!("Verified {} installed files", count))
+}
+
+fn read_install_manifest(
+    dot_dir: &DotDir,
+    opt_file_path: &Path,
+) -> Result<Option<InstallManifest>, Box<dyn Error>> {
+    let manifest_path = get_manifest_path(dot_dir, opt_file_path)?;
+    if !manifest_path.is_file() {
+        return Ok(None);
+    }
+    let manifest = toml::from_str(&read_to_string(&manifest_path)?)
+        .map_err(|e| format!("Invalid install record {}: {}", manifest_path.display(), e))?;
+    Ok(Some(manifest))
+}
+
+fn describe_signature(manifest: &InstallManifest) -> String {
+    match (&manifest.signature, &manifest.signature_key) {
+        (Signature::Verified, Some(key_id)) => format!("OK (signature verified with {})", key_id),
+        (Signature::Verified, None) => "OK (signature verified)".to_string(),
+        (Signature::Skipped, _) => "OK (signature not verified)".to_string(),
+        (Signature::Unsigned, _) => "OK (not signed)".to_string(),
+    }
+}
+
+// opt/<binary>/<version> has its record in var/<binary>/manifests/<version>.toml
+fn get_manifest_path(dot_dir: &DotDir, opt_file_path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let version = opt_file_path.file_name().ok_or("Invalid file path")?;
+    let binary_dir_name = opt_file_path
+        .parent()
+        .and_then(|p| p.file_name())
+        .ok_or("Invalid file path")?;
+    let manifest_path = dot_dir
+        .var
+        .join(binary_dir_name)
+        .join(MANIFESTS_DIR_NAME)
+        .join(format!("{}.toml", version.to_string_lossy()));
+    Ok(manifest_path)
+}
+
+const MANIFESTS_DIR_NAME: &str = "manifests";
+
+#[cfg(test)]
+mod tests {
+
+    use std::{fs::write, io::Write};
+
+    use tempfile::tempdir;
+
+    use crate::shared::select_binary_version;
+
+    use super::*;
+
+    #[test]
+    fn test_install_manifest() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let opt_file_path = dot_dir.opt.join(Binary::Terragrunt).join("0.38.4");
+        write(&opt_file_path, "terragrunt").unwrap();
+        let mut archive_file = tempfile::tempfile().unwrap();
+        archive_file.write_all(b"terragrunt").unwrap();
+        write_install_manifest(
+            &dot_dir,
+            &opt_file_path,
+            &archive_file,
+            Signature::Unsigned,
+            None,
+        )
+        .unwrap();
+        assert!(dot_dir
+            .var
+            .join("terragrunt/manifests/0.38.4.toml")
+            .is_file());
+        check_installed_file(&dot_dir, &opt_file_path).unwrap();
+        select_binary_version(Binary::Terragrunt, "0.38.4".to_string(), dot_dir.clone()).unwrap();
+        assert_eq!(
+            verify_installed_versions(None, None, dot_dir.clone()).unwrap(),
+            "Verified 2 installed files"
+        );
+
+        // Tampering is detected, also via the hard-linked bin/ file
+        write(&opt_file_path, "tampered").unwrap();
+        assert!(check_installed_file(&dot_dir, &opt_file_path).is_err());
+        assert!(verify_installed_versions(
+            Some(Binary::Terragrunt),
+            Some("0.38.4".to_string()),
+            dot_dir.clone()
+        )
+        .is_err());
+        assert!(
+            select_binary_version(Binary::Terragrunt, "0.38.4".to_string(), dot_dir.clone())
+                .is_err()
+        );
+
+        remove_install_manifest(&dot_dir, &opt_file_path).unwrap();
+        check_installed_file(&dot_dir, &opt_file_path).unwrap();
+    }
+
+    #[test]
+    fn test_install_manifest_failure_removes_binary() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let opt_file_path = dot_dir.opt.join(Binary::Terragrunt).join("0.38.4");
+        write(&opt_file_path, "terragrunt").unwrap();
+        // Manifests dir cannot be created where a file is
+        write(dot_dir.var.join("terragrunt/manifests"), "").unwrap();
+        let archive_file = tempfile::tempfile().unwrap();
+        assert!(write_install_manifest(
+            &dot_dir,
+            &opt_file_path,
+            &archive_file,
+            Signature::Unsigned,
+            None,
+        )
+        .is_err());
+        assert!(!opt_file_path.exists());
+    }
+}
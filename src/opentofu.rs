@@ -0,0 +1,252 @@
+use std::{error::Error, fs::File, io::Cursor};
+
+use pgp::{Deserializable, SignedPublicKey, StandaloneSignature};
+
+use crate::{
+    archive::{extract_binary_to_tmp_file, ArchiveType},
+    cache::Downloader,
+    keyring::{get_key_id, load_keyring, verify_signature, Keyring},
+    manifest::{write_install_manifest, Signature, SignatureKey},
+    shared::{Binary, DotDir, InstallOptions},
+    utils::{check_sha256_sum, get_expected_sha256, persist_tmp_path, wprintln},
+    version_check::check_new_binary_version,
+};
+
+use std::env::consts::EXE_SUFFIX;
+
+pub fn install_binary_version(
+    version: String,
+    dot_dir: DotDir,
+    os: String,
+    arch: String,
+    options: &InstallOptions,
+) -> Result<String, Box<dyn Error>> {
+    let opt_file_path = dot_dir.opt.join(Binary::OpenTofu).join(&version);
+    if !opt_file_path.exists() {
+        let zip_download_url =
+            get_release_file_url(&version, &get_zip_file_name(&version, &os, &arch));
+        let tmp_zip_file = tempfile::tempfile()?;
+        let mut downloader = Downloader::new(&dot_dir, options)?
+            .with_mirrors("opentofu", TOFU_RELEASES_DOWNLOAD_URL)?;
+        downloader.download_file(&zip_download_url, &tmp_zip_file)?;
+        // Without a keyring, the signature is not verified
+        let keyring = if options.skip_signature {
+            None
+        } else {
+            Some(load_keyring(&dot_dir, Keyring::OpenTofu)?)
+        };
+        let signature_key = verify_download_integrity(
+            &version,
+            &os,
+            &arch,
+            keyring.as_deref(),
+            &mut downloader,
+            &tmp_zip_file,
+        )?;
+        let file_name = format!("tofu{}", EXE_SUFFIX);
+        let tmp_opt_file = extract_binary_to_tmp_file(
+            tmp_zip_file.try_clone()?,
+            Some(ArchiveType::Zip),
+            &file_name,
+            &opt_file_path,
+        )?;
+        check_new_binary_version(&Binary::OpenTofu, &tmp_opt_file, &version, &os, &arch)?;
+        persist_tmp_path(tmp_opt_file, &opt_file_path)?;
+        let signature = match signature_key {
+            Some(_) => Signature::Verified,
+            None => Signature::Skipped,
+        };
+        write_install_manifest(
+            &dot_dir,
+            &opt_file_path,
+            &tmp_zip_file,
+            signature,
+            signature_key,
+        )?;
+        downloader.commit()?;
+    }
+    Ok(format!("Installed opentofu {}", version))
+}
+
+// OpenTofu signs SHA256SUMS both with GPG (.gpgsig) and cosign (.sig, .pem),
+// we verify the GPG signature, see https://opentofu.org/docs/intro/install/standalone/
+fn verify_download_integrity(
+    version: &str,
+    os: &str,
+    arch: &str,
+    keyring: Option<&[SignedPublicKey]>,
+    downloader: &mut Downloader,
+    zip_file: &File,
+) -> Result<SignatureKey, Box<dyn Error>> {
+    let shasums_file_name = format!("tofu_{}_SHA256SUMS", version);
+    let shasums_download_url = get_release_file_url(version, &shasums_file_name);
+    let shasums = downloader.get_text(&shasums_download_url)?;
+    let signature_key = match keyring {
+        Some(keyring) => Some(verify_shasums_signature(
+            version,
+            &shasums_file_name,
+            keyring,
+            downloader,
+            &shasums,
+        )?),
+        None => {
+            wprintln(&format!(
+                "Skipping PGP signature verification of opentofu {}",
+                version
+            ));
+            None
+        }
+    };
+    let expected_sha256 = get_expected_sha256(&shasums, &get_zip_file_name(version, os, arch))?;
+    check_sha256_sum(zip_file, &expected_sha256)?;
+    Ok(signature_key)
+}
+
+// Unlike HashiCorp's, OpenTofu's public key is not built in, an empty keyring
+// fails the install like a bad signature does. A valid signature by any key
+// of the keyring is accepted. Returns the key ID of the verifying key.
+fn verify_shasums_signature(
+    version: &str,
+    shasums_file_name: &str,
+    keyring: &[SignedPublicKey],
+    downloader: &mut Downloader,
+    shasums: &str,
+) -> Result<String, Box<dyn Error>> {
+    if keyring.is_empty() {
+        return Err(format!(
+            "No PGP key in opentofu keyring, unable to verify opentofu {}. Add OpenTofu's public key with 'terve key add tofu <FILE>', see https://github.com/superblk/terve#setup",
+            version
+        )
+        .into());
+    }
+    let shasums_sig_download_url =
+        get_release_file_url(version, &format!("{}.gpgsig", shasums_file_name));
+    let signature_bytes = downloader.get_bytes(&shasums_sig_download_url)?;
+    let signature = if signature_bytes.starts_with(b"-----BEGIN") {
+        StandaloneSignature::from_armor_single(Cursor::new(&signature_bytes))?.0
+    } else {
+        StandaloneSignature::from_bytes(&signature_bytes[..])?
+    };
+    let mut failures = Vec::new();
+    for public_key in keyring {
+        let pgp_key_id = get_key_id(public_key);
+        match verify_signature(shasums.as_bytes(), &signature, public_key) {
+            Ok(()) => return Ok(pgp_key_id),
+            Err(e) => failures.push(format!("{}: {}", pgp_key_id, e)),
+        }
+    }
+    Err(format!(
+        "No valid PGP signature found for opentofu {} ({}). See https://github.com/superblk/terve#setup",
+        version,
+        failures.join(", ")
+    )
+    .into())
+}
+
+fn get_zip_file_name(version: &str, os: &str, arch: &str) -> String {
+    format!("tofu_{}_{}_{}.zip", version, os, arch)
+}
+
+fn get_release_file_url(version: &str, file_name: &str) -> String {
+    format!("{}/v{}/{}", TOFU_RELEASES_DOWNLOAD_URL, version, file_name)
+}
+
+pub const TOFU_GIT_REPOSITORY_URL: &str = "https://github.com/opentofu/opentofu";
+
+const TOFU_RELEASES_DOWNLOAD_URL: &str = "https://github.com/opentofu/opentofu/releases/download";
+
+#[cfg(test)]
+mod tests {
+
+    use std::{
+        fs::{copy, create_dir_all, write},
+        io::Write,
+        path::Path,
+    };
+
+    use reqwest::Url;
+    use tempfile::{tempdir, TempDir};
+    use zip::{write::FileOptions, ZipWriter};
+
+    use crate::{keyring::add_key, shared::get_os_arch, utils::sha256_hex};
+
+    use super::*;
+
+    // Mirror serving an opentofu 1.6.0 release, with a signature that does
+    // not match its SHA256SUMS
+    fn setup_tofu_mirror(dot_dir: &DotDir) -> TempDir {
+        let (os, arch) = get_os_arch();
+        let mirror_dir = tempdir().unwrap();
+        let release_dir = mirror_dir.path().join("v1.6.0");
+        create_dir_all(&release_dir).unwrap();
+        let zip_file_path = release_dir.join(get_zip_file_name("1.6.0", &os, &arch));
+        let mut writer = ZipWriter::new(File::create(&zip_file_path).unwrap());
+        writer
+            .start_file(format!("tofu{}", EXE_SUFFIX), FileOptions::default())
+            .unwrap();
+        writer.write_all(b"tofu").unwrap();
+        writer.finish().unwrap();
+        let sha256 = sha256_hex(&File::open(&zip_file_path).unwrap()).unwrap();
+        write(
+            release_dir.join("tofu_1.6.0_SHA256SUMS"),
+            format!("{}  {}\n", sha256, get_zip_file_name("1.6.0", &os, &arch)),
+        )
+        .unwrap();
+        copy(
+            "tests/terraform_0.13.1_SHA256SUMS.72D7468F.sig",
+            release_dir.join("tofu_1.6.0_SHA256SUMS.gpgsig"),
+        )
+        .unwrap();
+        let mirror_url = Url::from_directory_path(mirror_dir.path()).unwrap();
+        write(
+            dot_dir.etc.join("config.toml"),
+            format!("[mirrors]\nopentofu = [\"{}\"]\n", mirror_url),
+        )
+        .unwrap();
+        mirror_dir
+    }
+
+    #[test]
+    fn test_install_requires_signature() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let _mirror_dir = setup_tofu_mirror(&dot_dir);
+        let (os, arch) = get_os_arch();
+        let install = || {
+            install_binary_version(
+                "1.6.0".to_string(),
+                dot_dir.clone(),
+                os.clone(),
+                arch.clone(),
+                &InstallOptions::default(),
+            )
+        };
+        let err = install().unwrap_err().to_string();
+        assert!(err.starts_with("No PGP key in opentofu keyring"), "{}", err);
+        add_key(
+            Binary::OpenTofu,
+            dot_dir.clone(),
+            Path::new("tests/hashicorp-72D7468F-refreshed.asc"),
+        )
+        .unwrap();
+        let err = install().unwrap_err().to_string();
+        assert!(
+            err.starts_with("No valid PGP signature found for opentofu 1.6.0 (72D7468F: "),
+            "{}",
+            err
+        );
+        assert!(!dot_dir.opt.join(Binary::OpenTofu).join("1.6.0").exists());
+    }
+
+    #[test]
+    fn test_release_file_urls() {
+        assert_eq!(
+            get_release_file_url("1.6.0", &get_zip_file_name("1.6.0", "linux", "amd64")),
+            "https://github.com/opentofu/opentofu/releases/download/v1.6.0/tofu_1.6.0_linux_amd64.zip"
+        );
+        assert_eq!(
+            get_release_file_url("1.6.0", "tofu_1.6.0_SHA256SUMS"),
+            "https://github.com/opentofu/opentofu/releases/download/v1.6.0/tofu_1.6.0_SHA256SUMS"
+        );
+    }
+}
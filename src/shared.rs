@@ -1,14 +1,32 @@
 use std::{
+    env::consts::{ARCH, OS},
     error::Error,
     fmt::Display,
-    fs::{create_dir_all, hard_link, read_dir, read_to_string, remove_file, write},
+    fs::{create_dir_all, read_dir, read_to_string, remove_file},
+    io::Write,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
 use semver::{Prerelease, Version};
 
-use crate::utils::{git_list_remote_tags, to_sorted_multiline_string};
+use crate::{
+    config::{Config, HttpConfig},
+    hashicorp::{
+        self, install_product_version, HashiCorpProduct, CONSUL, NOMAD, PACKER, TERRAFORM_LS, VAULT,
+    },
+    lock::{lock_binary, lock_version},
+    manifest::{check_installed_file, remove_install_manifest},
+    opentofu::{self, TOFU_GIT_REPOSITORY_URL},
+    shim::is_shim_enabled,
+    terraform::{self, TF_GIT_REPOSITORY_URL},
+    terragrunt::{self, TG_GIT_REPOSITORY_URL},
+    tool::{self, find_tool_descriptor, ToolDescriptor, TOOLS_DIR_NAME},
+    utils::{
+        create_tmp_file_for, git_list_remote_tags, hard_link_atomically, persist_tmp_file,
+        remove_stale_tmp_files, to_sorted_multiline_string, wprintln,
+    },
+};
 
 pub enum Action {
     List,
@@ -16,11 +34,24 @@ pub enum Action {
     Select,
     Remove,
     Which,
+    Resolve,
+    Shim,
+    Exec,
+    Use,
+    Cache,
+    Bundle,
+    Key,
+    Verify,
+    Doctor,
 }
 
+#[derive(Clone)]
 pub enum Binary {
     Terraform,
     Terragrunt,
+    OpenTofu,
+    HashiCorp(&'static HashiCorpProduct),
+    Tool(Box<ToolDescriptor>),
 }
 
 impl FromStr for Action {
@@ -33,8 +64,17 @@ impl FromStr for Action {
             "s" | "select" => Ok(Action::Select),
             "r" | "remove" => Ok(Action::Remove),
             "w" | "which" => Ok(Action::Which),
+            "resolve" => Ok(Action::Resolve),
+            "shim" => Ok(Action::Shim),
+            "x" | "exec" => Ok(Action::Exec),
+            "u" | "use" => Ok(Action::Use),
+            "cache" => Ok(Action::Cache),
+            "bundle" => Ok(Action::Bundle),
+            "key" => Ok(Action::Key),
+            "verify" => Ok(Action::Verify),
+            "doctor" => Ok(Action::Doctor),
             _ => Err(
-                "Action must be one of: l[ist], i[nstall], s[elect], r[emove] or w[hich]"
+                "Action must be one of: l[ist], i[nstall], s[elect], r[emove], w[hich], u[se], exec (x), resolve, shim, cache, bundle, key, verify or doctor"
                     .to_string(),
             ),
         }
@@ -48,7 +88,16 @@ impl FromStr for Binary {
         match a {
             "tf" | "terraform" => Ok(Binary::Terraform),
             "tg" | "terragrunt" => Ok(Binary::Terragrunt),
-            _ => Err("Binary must be one of: tf, tg, terraform or terragrunt".to_string()),
+            "tofu" | "opentofu" => Ok(Binary::OpenTofu),
+            "packer" => Ok(Binary::HashiCorp(&PACKER)),
+            "vault" => Ok(Binary::HashiCorp(&VAULT)),
+            "consul" => Ok(Binary::HashiCorp(&CONSUL)),
+            "nomad" => Ok(Binary::HashiCorp(&NOMAD)),
+            "terraform-ls" => Ok(Binary::HashiCorp(&TERRAFORM_LS)),
+            _ => Err(
+                "Binary must be one of: tf, tg, tofu, terraform, terragrunt, opentofu, packer, vault, consul, nomad or terraform-ls"
+                    .to_string(),
+            ),
         }
     }
 }
@@ -58,13 +107,16 @@ impl Display for Binary {
         match *self {
             Binary::Terraform => write!(f, "terraform"),
             Binary::Terragrunt => write!(f, "terragrunt"),
+            Binary::OpenTofu => write!(f, "opentofu"),
+            Binary::HashiCorp(product) => write!(f, "{}", product.name),
+            Binary::Tool(ref tool) => write!(f, "{}", tool.name),
         }
     }
 }
 
 impl AsRef<Path> for Binary {
     fn as_ref(&self) -> &Path {
-        let path = match *self {
+        let path = match self {
             Binary::Terraform => {
                 if cfg!(unix) {
                     "terraform"
@@ -79,11 +131,78 @@ impl AsRef<Path> for Binary {
                     "terragrunt.exe"
                 }
             }
+            Binary::OpenTofu => {
+                if cfg!(unix) {
+                    "tofu"
+                } else {
+                    "tofu.exe"
+                }
+            }
+            Binary::HashiCorp(product) => product.file_name,
+            Binary::Tool(tool) => &tool.file_name,
         };
         Path::new(path)
     }
 }
 
+pub const BINARIES: [Binary; 8] = [
+    Binary::Terraform,
+    Binary::Terragrunt,
+    Binary::OpenTofu,
+    Binary::HashiCorp(&PACKER),
+    Binary::HashiCorp(&VAULT),
+    Binary::HashiCorp(&CONSUL),
+    Binary::HashiCorp(&NOMAD),
+    Binary::HashiCorp(&TERRAFORM_LS),
+];
+
+impl Binary {
+    // Looks up binary by name, from built-in binaries first, then from tool
+    // descriptors in etc/tools.d
+    pub fn lookup(name: &str, dot_dir: &DotDir) -> Result<Binary, Box<dyn Error>> {
+        if let Ok(binary) = Binary::from_str(name) {
+            return Ok(binary);
+        }
+        match find_tool_descriptor(name, dot_dir)? {
+            Some(tool) => {
+                let binary = Binary::Tool(Box::new(tool));
+                create_dir_all(dot_dir.opt.join(&binary))?;
+                create_dir_all(dot_dir.var.join(&binary))?;
+                remove_stale_tmp_files(&dot_dir.opt.join(&binary))?;
+                remove_stale_tmp_files(&dot_dir.var.join(&binary))?;
+                Ok(binary)
+            }
+            None => Err(format!(
+                "{}, or a tool described in {}",
+                Binary::from_str(name).err().unwrap_or_default(),
+                dot_dir.etc.join(TOOLS_DIR_NAME).display()
+            )
+            .into()),
+        }
+    }
+
+    pub fn git_repository_url(&self) -> &str {
+        match self {
+            Binary::Terraform => TF_GIT_REPOSITORY_URL,
+            Binary::Terragrunt => TG_GIT_REPOSITORY_URL,
+            Binary::OpenTofu => TOFU_GIT_REPOSITORY_URL,
+            Binary::HashiCorp(product) => product.git_repository_url,
+            Binary::Tool(tool) => &tool.git_repository_url,
+        }
+    }
+}
+
+// Options for installing binaries
+#[derive(Clone, Default)]
+pub struct InstallOptions {
+    pub offline: bool,
+    // Skips mandatory PGP signature verification (--insecure-skip-signature)
+    pub skip_signature: bool,
+    // Download cache directory, defaults to var/cache
+    pub cache_dir: Option<PathBuf>,
+}
+
+#[derive(Clone)]
 pub struct DotDir {
     pub root: PathBuf,
     pub bin: PathBuf,
@@ -94,47 +213,181 @@ pub struct DotDir {
 
 impl DotDir {
     pub fn bootstrap(home_dir: &Path) -> Result<DotDir, Box<dyn Error>> {
+        let dot_dir = DotDir::new(home_dir);
+        create_dir_all(&dot_dir.bin)?;
+        create_dir_all(dot_dir.etc.join(TOOLS_DIR_NAME))?;
+        remove_stale_tmp_files(&dot_dir.bin)?;
+        for binary in BINARIES {
+            create_dir_all(dot_dir.opt.join(&binary))?;
+            create_dir_all(dot_dir.var.join(&binary))?;
+            remove_stale_tmp_files(&dot_dir.opt.join(&binary))?;
+            remove_stale_tmp_files(&dot_dir.var.join(&binary))?;
+        }
+        Ok(dot_dir)
+    }
+
+    // Resolves an already bootstrapped dot dir, without creating or cleaning
+    // up anything (e.g. for shims, which run on every binary invocation)
+    pub fn existing(home_dir: &Path) -> Result<DotDir, Box<dyn Error>> {
+        let dot_dir = DotDir::new(home_dir);
+        if !dot_dir.root.is_dir() {
+            return Err(format!("{} does not exist", dot_dir.root.display()).into());
+        }
+        Ok(dot_dir)
+    }
+
+    fn new(home_dir: &Path) -> DotDir {
         let root = home_dir.join(".terve");
-        let bin = root.join("bin");
-        let etc = root.join("etc");
-        let opt = root.join("opt");
-        let var = root.join("var");
-        create_dir_all(&bin)?;
-        create_dir_all(&etc)?;
-        create_dir_all(opt.join(Binary::Terraform))?;
-        create_dir_all(opt.join(Binary::Terragrunt))?;
-        create_dir_all(var.join(Binary::Terraform))?;
-        create_dir_all(var.join(Binary::Terragrunt))?;
-        Ok(DotDir {
+        DotDir {
+            bin: root.join("bin"),
+            etc: root.join("etc"),
+            opt: root.join("opt"),
+            var: root.join("var"),
             root,
-            bin,
-            etc,
-            opt,
-            var,
-        })
+        }
     }
 }
 
-pub fn list_available_versions(git_repo_url: &str) -> Result<String, Box<dyn Error>> {
-    let mut versions: Vec<Version> = git_list_remote_tags(git_repo_url)?
+pub fn install_binary_version(
+    binary: Binary,
+    version: String,
+    dot_dir: DotDir,
+    os: String,
+    arch: String,
+    options: &InstallOptions,
+) -> Result<String, Box<dyn Error>> {
+    let _lock = lock_version(&binary, &version, &dot_dir)?;
+    let opt_file_path = dot_dir.opt.join(&binary).join(&version);
+    let result = match binary {
+        Binary::Terraform => terraform::install_binary_version(
+            version.clone(),
+            dot_dir.clone(),
+            os.clone(),
+            arch.clone(),
+            options,
+        ),
+        Binary::Terragrunt => terragrunt::install_binary_version(
+            version.clone(),
+            dot_dir.clone(),
+            os.clone(),
+            arch.clone(),
+            options,
+        ),
+        Binary::OpenTofu => opentofu::install_binary_version(
+            version.clone(),
+            dot_dir.clone(),
+            os.clone(),
+            arch.clone(),
+            options,
+        ),
+        Binary::HashiCorp(product) => {
+            install_product_version(
+                product.name,
+                &opt_file_path,
+                &version,
+                &dot_dir,
+                &os,
+                &arch,
+                options,
+            )?;
+            Ok(format!("Installed {} {}", product.name, version))
+        }
+        Binary::Tool(ref tool) => tool::install_binary_version(
+            tool,
+            version.clone(),
+            dot_dir.clone(),
+            os.clone(),
+            arch.clone(),
+            options,
+        ),
+    }?;
+    Ok(result)
+}
+
+// Platform names as used in release file names, e.g. linux and amd64
+pub fn get_os_arch() -> (String, String) {
+    let os = match OS {
+        "linux" => "linux",
+        "macos" => "darwin",
+        "windows" => "windows",
+        other => panic!("Unsupported OS: {}", other),
+    };
+    let arch = match ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => panic!("Unsupported architecture: {}", other),
+    };
+    (os.to_string(), arch.to_string())
+}
+
+pub fn list_available_versions(
+    binary: &Binary,
+    dot_dir: &DotDir,
+    os: &str,
+    arch: &str,
+) -> Result<String, Box<dyn Error>> {
+    let mut versions = get_available_versions(binary, dot_dir, os, arch)?;
+    let result = to_sorted_multiline_string(&mut versions);
+    Ok(result)
+}
+
+// HashiCorp products are listed from the release index, which only lists
+// versions that have a build for given os and arch. Git tags are used for
+// other binaries, and as a fallback.
+pub fn get_available_versions(
+    binary: &Binary,
+    dot_dir: &DotDir,
+    os: &str,
+    arch: &str,
+) -> Result<Vec<Version>, Box<dyn Error>> {
+    let config = Config::load(dot_dir)?;
+    let product = match binary {
+        Binary::Terraform => Some("terraform"),
+        Binary::HashiCorp(product) => Some(product.name),
+        _ => None,
+    };
+    if let Some(product) = product {
+        match hashicorp::get_available_versions(product, os, arch, &config.http) {
+            Ok(versions) => return Ok(versions),
+            Err(e) => wprintln(&format!(
+                "Failed to list {} versions from release index ({}), falling back to git tags",
+                binary, e
+            )),
+        }
+    }
+    get_git_tag_versions(binary.git_repository_url(), &config.http)
+}
+
+fn get_git_tag_versions(
+    git_repo_url: &str,
+    http_config: &HttpConfig,
+) -> Result<Vec<Version>, Box<dyn Error>> {
+    let versions: Vec<Version> = git_list_remote_tags(git_repo_url, http_config)?
         .iter()
         .map(|t| t.trim_start_matches('v'))
         .filter_map(|s| Version::parse(s).ok())
         .filter(|v| v.pre == Prerelease::EMPTY)
         .collect();
-    let result = to_sorted_multiline_string(&mut versions);
-    Ok(result)
+    Ok(versions)
 }
 
 pub fn list_installed_versions(binary: Binary, dot_dir: DotDir) -> Result<String, Box<dyn Error>> {
+    let mut installed_versions = get_installed_versions(&binary, &dot_dir)?;
+    let result = to_sorted_multiline_string(&mut installed_versions);
+    Ok(result)
+}
+
+pub fn get_installed_versions(
+    binary: &Binary,
+    dot_dir: &DotDir,
+) -> Result<Vec<Version>, Box<dyn Error>> {
     let opt_dir = dot_dir.opt.join(binary);
-    let mut installed_versions: Vec<Version> = read_dir(&opt_dir)?
+    let installed_versions: Vec<Version> = read_dir(&opt_dir)?
         .filter_map(|r| Some(r.ok()?.path()))
         .filter_map(|p| Some(p.strip_prefix(&opt_dir).ok()?.to_owned()))
         .filter_map(|p| Version::parse(p.to_string_lossy().as_ref()).ok())
         .collect();
-    let result = to_sorted_multiline_string(&mut installed_versions);
-    Ok(result)
+    Ok(installed_versions)
 }
 
 pub fn select_binary_version(
@@ -142,29 +395,75 @@ pub fn select_binary_version(
     version: String,
     dot_dir: DotDir,
 ) -> Result<String, Box<dyn Error>> {
+    let _lock = lock_binary(&binary, &dot_dir)?;
     let opt_file_path = dot_dir.opt.join(&binary).join(&version);
     if !opt_file_path.exists() {
         return Err(format!("{0} version {1} is not installed", binary, version).into());
     }
-    let bin_file_path = dot_dir.bin.join(&binary);
-    if bin_file_path.exists() {
-        remove_file(&bin_file_path)?;
-    }
-    hard_link(&opt_file_path, &bin_file_path)?;
+    check_installed_file(&dot_dir, &opt_file_path).map_err(|e| {
+        format!(
+            "{0}. Reinstall it with 'terve r {1} {2}' and 'terve i {1} {2}'",
+            e, binary, version
+        )
+    })?;
+    // Version file is prepared first, and renamed into place right after the
+    // link, so that a failure leaves both the link and version file untouched
     let version_file_path = dot_dir.var.join(&binary).join("version");
-    write(version_file_path, &version)?;
+    let mut version_tmp_file = create_tmp_file_for(&version_file_path)?;
+    version_tmp_file.write_all(version.as_bytes())?;
+    version_tmp_file.as_file().sync_all()?;
+    // In shim mode, bin/<binary> dispatches based on the version file only
+    if !is_shim_enabled(&binary, &dot_dir) {
+        let bin_file_path = dot_dir.bin.join(&binary);
+        hard_link_atomically(&opt_file_path, &bin_file_path)?;
+    }
+    persist_tmp_file(version_tmp_file, &version_file_path)?;
     Ok(format!("Selected {} {}", binary, version))
 }
 
+// Installs (if not installed) and selects given version. As selection is
+// atomic, the previous selection is left as it was if anything fails.
+pub fn use_binary_version(
+    binary: Binary,
+    version: String,
+    dot_dir: DotDir,
+    os: String,
+    arch: String,
+    options: &InstallOptions,
+) -> Result<String, Box<dyn Error>> {
+    let old_version = get_selected_version(binary.clone(), dot_dir.clone())?;
+    install_binary_version(
+        binary.clone(),
+        version.clone(),
+        dot_dir.clone(),
+        os,
+        arch,
+        options,
+    )?;
+    select_binary_version(binary.clone(), version.clone(), dot_dir)?;
+    let old_version = if old_version.is_empty() {
+        "(none)".to_string()
+    } else {
+        old_version
+    };
+    Ok(format!(
+        "Switched {} from {} to {}",
+        binary, old_version, version
+    ))
+}
+
 pub fn remove_binary_version(
     binary: Binary,
     version: String,
     dot_dir: DotDir,
 ) -> Result<String, Box<dyn Error>> {
+    let _binary_lock = lock_binary(&binary, &dot_dir)?;
+    let _version_lock = lock_version(&binary, &version, &dot_dir)?;
     let opt_file_path = dot_dir.opt.join(&binary).join(&version);
     if opt_file_path.exists() {
         remove_file(&opt_file_path)?;
     }
+    remove_install_manifest(&dot_dir, &opt_file_path)?;
     Ok(format!("Removed {} {}", binary, version))
 }
 
@@ -178,3 +477,134 @@ pub fn get_selected_version(binary: Binary, dot_dir: DotDir) -> Result<String, B
     };
     Ok(result)
 }
+
+// Installs run the installed stub scripts
+#[cfg(all(test, unix))]
+mod tests {
+
+    use std::{env::consts::EXE_SUFFIX, fs::write};
+
+    use reqwest::Url;
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    // File mirror serving terragrunt stub scripts that report given versions.
+    // Only releases without SHA256SUMS (before 0.18.1) can be served.
+    fn setup_terragrunt_mirror(dot_dir: &DotDir, releases: &[(&str, &str)]) -> TempDir {
+        let mirror_dir = tempdir().unwrap();
+        let (os, arch) = get_os_arch();
+        let file_name = format!("terragrunt_{}_{}{}", os, arch, EXE_SUFFIX);
+        for (version, reported_version) in releases {
+            let release_dir = mirror_dir.path().join(format!("v{}", version));
+            create_dir_all(&release_dir).unwrap();
+            write(
+                release_dir.join(&file_name),
+                format!(
+                    "#!/bin/sh\necho \"terragrunt version v{}\"\n",
+                    reported_version
+                ),
+            )
+            .unwrap();
+        }
+        let mirror_url = Url::from_directory_path(mirror_dir.path()).unwrap();
+        write(
+            dot_dir.etc.join("config.toml"),
+            format!("[mirrors]\nterragrunt = [\"{}\"]\n", mirror_url),
+        )
+        .unwrap();
+        mirror_dir
+    }
+
+    #[test]
+    fn test_existing_dot_dir() {
+        let home = tempdir().unwrap();
+        assert!(DotDir::existing(home.path()).is_err());
+        assert!(!home.path().join(".terve").exists());
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let existing_dot_dir = DotDir::existing(home.path()).unwrap();
+        assert_eq!(existing_dot_dir.opt, dot_dir.opt);
+        assert_eq!(existing_dot_dir.var, dot_dir.var);
+    }
+
+    #[test]
+    fn test_use_binary_version() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let _mirror_dir =
+            setup_terragrunt_mirror(&dot_dir, &[("0.17.4", "0.17.4"), ("0.18.0", "0.18.0")]);
+        let (os, arch) = get_os_arch();
+        let options = InstallOptions::default();
+        let use_version = |version: &str| {
+            use_binary_version(
+                Binary::Terragrunt,
+                version.to_string(),
+                dot_dir.clone(),
+                os.clone(),
+                arch.clone(),
+                &options,
+            )
+        };
+        assert_eq!(
+            use_version("0.17.4").unwrap(),
+            "Switched terragrunt from (none) to 0.17.4"
+        );
+        assert_eq!(
+            use_version("0.18.0").unwrap(),
+            "Switched terragrunt from 0.17.4 to 0.18.0"
+        );
+        // Already installed, only selected
+        assert_eq!(
+            use_version("0.17.4").unwrap(),
+            "Switched terragrunt from 0.18.0 to 0.17.4"
+        );
+        // Failed install leaves the selection as it was
+        assert!(use_version("0.16.0").is_err());
+        assert_eq!(
+            get_selected_version(Binary::Terragrunt, dot_dir.clone()).unwrap(),
+            "0.17.4"
+        );
+        assert_eq!(
+            read_to_string(dot_dir.bin.join(Binary::Terragrunt)).unwrap(),
+            read_to_string(dot_dir.opt.join(Binary::Terragrunt).join("0.17.4")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_install_rolls_back_wrong_version() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let _mirror_dir = setup_terragrunt_mirror(&dot_dir, &[("0.18.0", "0.17.4")]);
+        let cache_dir = tempdir().unwrap();
+        let (os, arch) = get_os_arch();
+        let options = InstallOptions {
+            cache_dir: Some(cache_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let err = install_binary_version(
+            Binary::Terragrunt,
+            "0.18.0".to_string(),
+            dot_dir.clone(),
+            os,
+            arch,
+            &options,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("reports version 0.17.4"));
+        // Neither the binary nor its temporary file is left in opt/
+        assert_eq!(
+            read_dir(dot_dir.opt.join(Binary::Terragrunt))
+                .unwrap()
+                .count(),
+            0
+        );
+        assert!(!dot_dir
+            .var
+            .join(Binary::Terragrunt)
+            .join("manifests")
+            .join("0.18.0.toml")
+            .exists());
+        // Bad artifact is not cached either
+        assert_eq!(read_dir(cache_dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    env::{args_os, current_dir, current_exe},
+    error::Error,
+    ffi::OsString,
+    fs::{read_to_string, remove_file, write, File},
+    path::Path,
+    str::FromStr,
+};
+
+use dirs::home_dir;
+use semver::Version;
+
+use crate::{
+    exec::exec_binary,
+    lock::lock_binary,
+    shared::{get_installed_versions, Binary, DotDir},
+    tool::{load_tool_descriptor, TOOLS_DIR_NAME},
+    utils::{hard_link_atomically, write_file_atomically},
+    version::{get_wanted_version, resolve_version},
+};
+
+// In shim mode, bin/<binary> is a copy of the terve executable. When invoked
+// by the binary's name, terve resolves the wanted version (environment
+// variable, version files walking up from current directory, then the global
+// selection) and runs the matching binary from opt/<binary>.
+
+pub fn get_shim_binary() -> Option<Binary> {
+    let arg0 = args_os().next()?;
+    get_binary_for_arg0(Path::new(&arg0), &home_dir()?)
+}
+
+// Binary named exactly like the executable (e.g. terraform, not tf)
+fn get_binary_for_arg0(arg0: &Path, home: &Path) -> Option<Binary> {
+    let name = arg0.file_stem()?.to_str()?.to_owned();
+    let binary = match Binary::from_str(&name) {
+        Ok(binary) => binary,
+        // Only a tool with a descriptor, without bootstrapping ~/.terve (e.g.
+        // for a renamed terve executable, such as terve_linux_amd64)
+        Err(_) if name != env!("CARGO_PKG_NAME") => {
+            let descriptor_path = home
+                .join(".terve")
+                .join("etc")
+                .join(TOOLS_DIR_NAME)
+                .join(format!("{}.toml", name));
+            if !descriptor_path.is_file() {
+                return None;
+            }
+            Binary::Tool(Box::new(load_tool_descriptor(&descriptor_path).ok()?))
+        }
+        Err(_) => return None,
+    };
+    Some(binary).filter(|b| Path::new(b.as_ref()).file_stem() == Some(name.as_ref()))
+}
+
+pub fn run_shim(binary: Binary) -> Result<i32, Box<dyn Error>> {
+    let home = home_dir().ok_or("Unable to resolve user home directory")?;
+    let dot_dir = DotDir::existing(&home)?;
+    let (spec, source) = match get_wanted_version(&binary, &current_dir()?)? {
+        Some(wanted) => wanted,
+        None => {
+            let version_file_path = dot_dir.var.join(&binary).join("version");
+            if !version_file_path.exists() {
+                return Err(format!(
+                    "No {0} version selected. Run 'terve s {0} <version>' or add a .{0}-version file",
+                    binary
+                )
+                .into());
+            }
+            let version = read_to_string(version_file_path)?.trim().to_string();
+            (version, "terve select".to_string())
+        }
+    };
+    let version = if Version::parse(&spec).is_ok() {
+        spec
+    } else {
+        let installed_versions = get_installed_versions(&binary, &dot_dir)?;
+        resolve_version(&binary, &spec, &installed_versions)?
+    };
+    let opt_file_path = dot_dir.opt.join(&binary).join(&version);
+    if !opt_file_path.exists() {
+        return Err(format!(
+            "{0} version {1} (set by {2}) is not installed. Run 'terve i {0} {1}'",
+            binary, version, source
+        )
+        .into());
+    }
+    let args: Vec<OsString> = args_os().skip(1).collect();
+    exec_binary(&opt_file_path, &args, &[])
+}
+
+pub fn is_shim_enabled(binary: &Binary, dot_dir: &DotDir) -> bool {
+    dot_dir
+        .var
+        .join(binary)
+        .join(SHIM_MARKER_FILE_NAME)
+        .exists()
+}
+
+pub fn enable_shim(binary: Binary, dot_dir: DotDir) -> Result<String, Box<dyn Error>> {
+    enable_shim_from(binary, dot_dir, &current_exe()?)
+}
+
+// Given terve executable is copied over bin/<binary> atomically, so that
+// bin/<binary> is never missing or partially written
+fn enable_shim_from(
+    binary: Binary,
+    dot_dir: DotDir,
+    exe_path: &Path,
+) -> Result<String, Box<dyn Error>> {
+    let _lock = lock_binary(&binary, &dot_dir)?;
+    let bin_file_path = dot_dir.bin.join(&binary);
+    write_file_atomically(&bin_file_path, &mut File::open(exe_path)?, true)?;
+    write(dot_dir.var.join(&binary).join(SHIM_MARKER_FILE_NAME), "")?;
+    Ok(format!("Enabled shim mode for {}", binary))
+}
+
+pub fn disable_shim(binary: Binary, dot_dir: DotDir) -> Result<String, Box<dyn Error>> {
+    let _lock = lock_binary(&binary, &dot_dir)?;
+    let bin_file_path = dot_dir.bin.join(&binary);
+    let version_file_path = dot_dir.var.join(&binary).join("version");
+    let opt_file_path = if version_file_path.exists() {
+        let version = read_to_string(version_file_path)?;
+        Some(dot_dir.opt.join(&binary).join(version.trim())).filter(|p| p.exists())
+    } else {
+        None
+    };
+    // Selected version replaces the shim atomically, without a selected
+    // version there is nothing to link
+    match opt_file_path {
+        Some(opt_file_path) => hard_link_atomically(&opt_file_path, &bin_file_path)?,
+        None if bin_file_path.exists() => remove_file(&bin_file_path)?,
+        None => {}
+    }
+    let shim_marker_path = dot_dir.var.join(&binary).join(SHIM_MARKER_FILE_NAME);
+    if shim_marker_path.exists() {
+        remove_file(&shim_marker_path)?;
+    }
+    Ok(format!("Disabled shim mode for {}", binary))
+}
+
+const SHIM_MARKER_FILE_NAME: &str = "shim";
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::read_dir;
+
+    use tempfile::tempdir;
+
+    use crate::shared::select_binary_version;
+
+    use super::*;
+
+    #[test]
+    fn test_get_binary_for_arg0() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let bin_dir = dot_dir.bin.clone();
+        assert!(matches!(
+            get_binary_for_arg0(&bin_dir.join("terraform"), home.path()),
+            Some(Binary::Terraform)
+        ));
+        assert!(matches!(
+            get_binary_for_arg0(&bin_dir.join("terragrunt"), home.path()),
+            Some(Binary::Terragrunt)
+        ));
+        // Aliases are not shims, and terve itself is not a shim
+        assert!(get_binary_for_arg0(&bin_dir.join("tf"), home.path()).is_none());
+        assert!(get_binary_for_arg0(Path::new("/usr/local/bin/terve"), home.path()).is_none());
+        assert!(get_binary_for_arg0(Path::new("tflint"), home.path()).is_none());
+        write(
+            dot_dir.etc.join(TOOLS_DIR_NAME).join("tflint.toml"),
+            "aliases = [\"tfl\"]\ngit_repository_url = \"x\"\ndownload_url = \"y\"\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            get_binary_for_arg0(Path::new("tflint"), home.path()),
+            Some(Binary::Tool(t)) if t.name == "tflint"
+        ));
+        assert!(get_binary_for_arg0(Path::new("tfl"), home.path()).is_none());
+    }
+
+    #[test]
+    fn test_get_binary_for_arg0_does_not_bootstrap() {
+        let home = tempdir().unwrap();
+        assert!(get_binary_for_arg0(Path::new("terve_linux_amd64"), home.path()).is_none());
+        assert!(get_binary_for_arg0(Path::new("tflint"), home.path()).is_none());
+        assert!(!home.path().join(".terve").exists());
+    }
+
+    #[test]
+    fn test_enable_disable_shim() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let exe_dir = tempdir().unwrap();
+        let exe_path = exe_dir.path().join("terve");
+        write(&exe_path, "terve").unwrap();
+        write(
+            dot_dir.opt.join(Binary::Terragrunt).join("0.38.4"),
+            "terragrunt",
+        )
+        .unwrap();
+        select_binary_version(Binary::Terragrunt, "0.38.4".to_string(), dot_dir.clone()).unwrap();
+        let bin_file_path = dot_dir.bin.join(Binary::Terragrunt);
+        enable_shim_from(Binary::Terragrunt, dot_dir.clone(), &exe_path).unwrap();
+        assert!(is_shim_enabled(&Binary::Terragrunt, &dot_dir));
+        assert_eq!(read_to_string(&bin_file_path).unwrap(), "terve");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = bin_file_path.metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+        // Selected version is not touched by the copy
+        assert_eq!(
+            read_to_string(dot_dir.opt.join(Binary::Terragrunt).join("0.38.4")).unwrap(),
+            "terragrunt"
+        );
+        disable_shim(Binary::Terragrunt, dot_dir.clone()).unwrap();
+        assert!(!is_shim_enabled(&Binary::Terragrunt, &dot_dir));
+        assert_eq!(read_to_string(&bin_file_path).unwrap(), "terragrunt");
+        // No temporary files are left behind
+        assert_eq!(read_dir(&dot_dir.bin).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_select_in_shim_mode_keeps_shim() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let opt_file_path = dot_dir.opt.join(Binary::Terragrunt).join("0.38.4");
+        write(&opt_file_path, "terragrunt").unwrap();
+        let bin_file_path = dot_dir.bin.join(Binary::Terragrunt);
+        write(&bin_file_path, "shim").unwrap();
+        write(
+            dot_dir
+                .var
+                .join(Binary::Terragrunt)
+                .join(SHIM_MARKER_FILE_NAME),
+            "",
+        )
+        .unwrap();
+        assert!(is_shim_enabled(&Binary::Terragrunt, &dot_dir));
+        select_binary_version(Binary::Terragrunt, "0.38.4".to_string(), dot_dir.clone()).unwrap();
+        assert_eq!(read_to_string(&bin_file_path).unwrap(), "shim");
+        assert_eq!(
+            read_to_string(dot_dir.var.join(Binary::Terragrunt).join("version")).unwrap(),
+            "0.38.4"
+        );
+
+        // Disabling shim mode links the selected version
+        disable_shim(Binary::Terragrunt, dot_dir.clone()).unwrap();
+        assert!(!is_shim_enabled(&Binary::Terragrunt, &dot_dir));
+        assert_eq!(read_to_string(&bin_file_path).unwrap(), "terragrunt");
+    }
+}
@@ -1,16 +1,14 @@
-use std::{
-    error::Error,
-    fs::File,
-    io::{copy, Seek, SeekFrom},
-};
+use std::error::Error;
 
 use crate::{
-    http::HttpClient,
-    shared::{Binary, DotDir},
-    utils::{check_sha256_sum, regex_capture_group, wprintln},
+    archive::{extract_binary_to_tmp_file, ArchiveType},
+    cache::Downloader,
+    manifest::{write_install_manifest, Signature},
+    shared::{Binary, DotDir, InstallOptions},
+    utils::{check_sha256_sum, get_expected_sha256, persist_tmp_path, wprintln},
+    version_check::check_new_binary_version,
 };
-use regex::Regex;
-use reqwest::StatusCode;
+use semver::Version;
 use std::env::consts::EXE_SUFFIX;
 
 pub fn install_binary_version(
@@ -18,6 +16,7 @@ pub fn install_binary_version(
     dot_dir: DotDir,
     os: String,
     arch: String,
+    options: &InstallOptions,
 ) -> Result<String, Box<dyn Error>> {
     let opt_file_path = dot_dir.opt.join(Binary::Terragrunt).join(&version);
     if !opt_file_path.exists() {
@@ -25,36 +24,194 @@ pub fn install_binary_version(
         let file_download_url = format!("{}/v{}/{}", TG_RELEASES_DOWNLOAD_URL, version, file_name);
         let shasums_download_url =
             format!("{0}/v{1}/SHA256SUMS", TG_RELEASES_DOWNLOAD_URL, version);
-        let mut tmp_file = tempfile::tempfile()?;
-        let http_client = HttpClient::new()?;
-        http_client.download_file(&file_download_url, &tmp_file)?;
-        match http_client.get_text(&shasums_download_url) {
-            Ok(shasums) => {
-                let sha256_regex = Regex::new(format!(r"([a-f0-9]+)\s+{}", file_name).as_str())?;
-                let expected_sha256 = regex_capture_group(&sha256_regex, 1, &shasums)?;
-                check_sha256_sum(&tmp_file, &expected_sha256)?;
-            }
-            Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => {
-                wprintln("Skipping SHA256 file integrity check. See https://github.com/superblk/terve#install");
-            }
-            Err(other) => {
-                return Err(other.into());
-            }
-        }
-        tmp_file.seek(SeekFrom::Start(0))?;
-        let mut opt_file = File::create(&opt_file_path)?;
-        copy(&mut tmp_file, &mut opt_file)?;
-        #[cfg(unix)]
-        {
-            use std::fs::{set_permissions, Permissions};
-            use std::os::unix::fs::PermissionsExt;
-            set_permissions(&opt_file_path, Permissions::from_mode(0o755))?;
+        let tmp_file = tempfile::tempfile()?;
+        let mut downloader = Downloader::new(&dot_dir, options)?
+            .with_mirrors("terragrunt", TG_RELEASES_DOWNLOAD_URL)?;
+        downloader.download_file(&file_download_url, &tmp_file)?;
+        // Not fetched at all for old releases, so that offline installs (and
+        // bundle imports) do not need a cached SHA256SUMS that never existed
+        if has_shasums(&version)? {
+            let shasums = downloader.get_text(&shasums_download_url)?;
+            let expected_sha256 = get_expected_sha256(&shasums, &file_name)?;
+            check_sha256_sum(&tmp_file, &expected_sha256)?;
+        } else {
+            wprintln("Skipping SHA256 file integrity check. See https://github.com/superblk/terve#install");
         }
+        let tmp_opt_file = extract_binary_to_tmp_file(
+            tmp_file.try_clone()?,
+            Some(ArchiveType::Binary),
+            &file_name,
+            &opt_file_path,
+        )?;
+        check_new_binary_version(&Binary::Terragrunt, &tmp_opt_file, &version, &os, &arch)?;
+        persist_tmp_path(tmp_opt_file, &opt_file_path)?;
+        write_install_manifest(
+            &dot_dir,
+            &opt_file_path,
+            &tmp_file,
+            Signature::Unsigned,
+            None,
+        )?;
+        downloader.commit()?;
     }
     Ok(format!("Installed terragrunt {}", version))
 }
 
+// Releases before 0.18.1 do not ship SHA256SUMS, for later releases (from any
+// mirror) the checksum must be verified
+fn has_shasums(version: &str) -> Result<bool, Box<dyn Error>> {
+    Ok(Version::parse(version)? >= Version::new(0, 18, 1))
+}
+
 pub const TG_GIT_REPOSITORY_URL: &str = "https://github.com/gruntwork-io/terragrunt";
 
 const TG_RELEASES_DOWNLOAD_URL: &str =
     "https://github.com/gruntwork-io/terragrunt/releases/download";
+
+#[cfg(test)]
+mod tests {
+
+    #[cfg(unix)]
+    use std::fs::{create_dir_all, remove_file, write, File};
+
+    #[cfg(unix)]
+    use reqwest::Url;
+    #[cfg(unix)]
+    use tempfile::tempdir;
+
+    #[cfg(unix)]
+    use crate::{shared::get_os_arch, utils::sha256_hex};
+
+    use super::*;
+
+    // Installed binaries are run to check their version
+    #[cfg(unix)]
+    fn stub_script(version: &str) -> String {
+        format!("#!/bin/sh\necho \"terragrunt version v{}\"\n", version)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_from_mirror_without_shasums() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let mirror_dir = tempdir().unwrap();
+        let (os, arch) = get_os_arch();
+        let file_name = format!("terragrunt_{}_{}{}", os, arch, EXE_SUFFIX);
+        for version in ["0.38.4", "0.18.0"] {
+            let release_dir = mirror_dir.path().join(format!("v{}", version));
+            create_dir_all(&release_dir).unwrap();
+            write(release_dir.join(&file_name), stub_script(version)).unwrap();
+        }
+        let mirror_url = Url::from_directory_path(mirror_dir.path()).unwrap();
+        write(
+            dot_dir.etc.join("config.toml"),
+            format!("[mirrors]\nterragrunt = [\"{}\"]\n", mirror_url),
+        )
+        .unwrap();
+        let options = InstallOptions::default();
+        assert!(install_binary_version(
+            "0.38.4".to_string(),
+            dot_dir.clone(),
+            os.clone(),
+            arch.clone(),
+            &options
+        )
+        .is_err());
+        assert!(!dot_dir.opt.join(Binary::Terragrunt).join("0.38.4").exists());
+        install_binary_version("0.18.0".to_string(), dot_dir.clone(), os, arch, &options).unwrap();
+        assert!(dot_dir.opt.join(Binary::Terragrunt).join("0.18.0").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_offline_reinstall_without_shasums() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let mirror_dir = tempdir().unwrap();
+        let (os, arch) = get_os_arch();
+        let file_name = format!("terragrunt_{}_{}{}", os, arch, EXE_SUFFIX);
+        create_dir_all(mirror_dir.path().join("v0.18.0")).unwrap();
+        write(
+            mirror_dir.path().join("v0.18.0").join(&file_name),
+            stub_script("0.18.0"),
+        )
+        .unwrap();
+        let mirror_url = Url::from_directory_path(mirror_dir.path()).unwrap();
+        write(
+            dot_dir.etc.join("config.toml"),
+            format!("[mirrors]\nterragrunt = [\"{}\"]\n", mirror_url),
+        )
+        .unwrap();
+        install_binary_version(
+            "0.18.0".to_string(),
+            dot_dir.clone(),
+            os.clone(),
+            arch.clone(),
+            &InstallOptions::default(),
+        )
+        .unwrap();
+        let opt_file_path = dot_dir.opt.join(Binary::Terragrunt).join("0.18.0");
+        remove_file(&opt_file_path).unwrap();
+        let offline_options = InstallOptions {
+            offline: true,
+            ..Default::default()
+        };
+        install_binary_version(
+            "0.18.0".to_string(),
+            dot_dir.clone(),
+            os,
+            arch,
+            &offline_options,
+        )
+        .unwrap();
+        assert!(opt_file_path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_from_mirror_with_shasums() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let mirror_dir = tempdir().unwrap();
+        let (os, arch) = get_os_arch();
+        let file_name = format!("terragrunt_{}_{}{}", os, arch, EXE_SUFFIX);
+        let release_dir = mirror_dir.path().join("v0.38.4");
+        create_dir_all(&release_dir).unwrap();
+        write(release_dir.join(&file_name), stub_script("0.38.4")).unwrap();
+        let sha256 = sha256_hex(&File::open(release_dir.join(&file_name)).unwrap()).unwrap();
+        // Archive of the same name prefix is listed first
+        write(
+            release_dir.join("SHA256SUMS"),
+            format!(
+                "{0}  {1}.tar.gz\n{2}  {1}\n",
+                "0".repeat(64),
+                file_name,
+                sha256
+            ),
+        )
+        .unwrap();
+        let mirror_url = Url::from_directory_path(mirror_dir.path()).unwrap();
+        write(
+            dot_dir.etc.join("config.toml"),
+            format!("[mirrors]\nterragrunt = [\"{}\"]\n", mirror_url),
+        )
+        .unwrap();
+        install_binary_version(
+            "0.38.4".to_string(),
+            dot_dir.clone(),
+            os,
+            arch,
+            &InstallOptions::default(),
+        )
+        .unwrap();
+        assert!(dot_dir.opt.join(Binary::Terragrunt).join("0.38.4").exists());
+    }
+
+    #[test]
+    fn test_has_shasums() {
+        assert!(!has_shasums("0.18.0").unwrap());
+        assert!(has_shasums("0.18.1").unwrap());
+        assert!(has_shasums("0.38.4").unwrap());
+    }
+}
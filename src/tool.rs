@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{read_dir, read_to_string},
+    path::Path,
+};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+    archive::{extract_binary, ArchiveType},
+    cache::Downloader,
+    manifest::{write_install_manifest, Signature},
+    shared::{Binary, DotDir, InstallOptions},
+    utils::{check_sha256_sum, regex_capture_group, wprintln},
+};
+
+use std::env::consts::EXE_SUFFIX;
+
+// Tool descriptors are TOML files in etc/tools.d, describing how to list and
+// install a binary released on GitHub (or similar), e.g. tflint.toml:
+//
+//   git_repository_url = "https://github.com/terraform-linters/tflint"
+//   download_url = "https://github.com/terraform-linters/tflint/releases/download/v{version}/tflint_{os}_{arch}.zip"
+//   checksum_url = "https://github.com/terraform-linters/tflint/releases/download/v{version}/checksums.txt"
+//
+// The tool is named after the file. Templates may contain {version}, {os} and
+// {arch} placeholders, os and arch names can be overridden per tool.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolDescriptor {
+    #[serde(skip)]
+    pub name: String,
+    #[serde(skip)]
+    pub file_name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub git_repository_url: String,
+    pub download_url: String,
+    pub archive: Option<ArchiveType>,
+    pub binary_path: Option<String>,
+    pub checksum_url: Option<String>,
+    #[serde(default)]
+    pub os_names: HashMap<String, String>,
+    #[serde(default)]
+    pub arch_names: HashMap<String, String>,
+}
+
+impl ToolDescriptor {
+    fn render(&self, template: &str, version: &str, os: &str, arch: &str) -> String {
+        let os = self.os_names.get(os).map(String::as_str).unwrap_or(os);
+        let arch = self
+            .arch_names
+            .get(arch)
+            .map(String::as_str)
+            .unwrap_or(arch);
+        template
+            .replace("{version}", version)
+            .replace("{os}", os)
+            .replace("{arch}", arch)
+    }
+}
+
+// Looks up tool descriptor by name or alias
+pub fn find_tool_descriptor(
+    name: &str,
+    dot_dir: &DotDir,
+) -> Result<Option<ToolDescriptor>, Box<dyn Error>> {
+    let tool = load_tool_descriptors(dot_dir)?
+        .into_iter()
+        .find(|t| t.name == name || t.aliases.iter().any(|a| a == name));
+    Ok(tool)
+}
+
+pub fn load_tool_descriptors(dot_dir: &DotDir) -> Result<Vec<ToolDescriptor>, Box<dyn Error>> {
+    let tools_dir = dot_dir.etc.join(TOOLS_DIR_NAME);
+    if !tools_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut paths: Vec<_> = read_dir(&tools_dir)?
+        .filter_map(|r| Some(r.ok()?.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .collect();
+    paths.sort();
+    paths.iter().map(|p| load_tool_descriptor(p)).collect()
+}
+
+pub fn load_tool_descriptor(path: &Path) -> Result<ToolDescriptor, Box<dyn Error>> {
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let mut tool: ToolDescriptor = toml::from_str(&read_to_string(path)?)
+        .map_err(|e| format!("Invalid tool descriptor {}: {}", path.display(), e))?;
+    let name_regex = Regex::new(r"^[a-z0-9][a-z0-9_-]*$")?;
+    for n in std::iter::once(&name).chain(&tool.aliases) {
+        if !name_regex.is_match(n) {
+            return Err(format!(
+                "Invalid tool descriptor {}: invalid tool name '{}'",
+                path.display(),
+                n
+            )
+            .into());
+        }
+        if n.parse::<Binary>().is_ok() {
+            return Err(format!(
+                "Invalid tool descriptor {}: '{}' is reserved for a built-in binary",
+                path.display(),
+                n
+            )
+            .into());
+        }
+    }
+    tool.file_name = format!("{}{}", name, EXE_SUFFIX);
+    tool.name = name;
+    Ok(tool)
+}
+
+pub fn install_binary_version(
+    tool: &ToolDescriptor,
+    version: String,
+    dot_dir: DotDir,
+    os: String,
+    arch: String,
+    options: &InstallOptions,
+) -> Result<String, Box<dyn Error>> {
+    let opt_file_path = dot_dir.opt.join(&tool.file_name).join(&version);
+    if !opt_file_path.exists() {
+        let download_url = tool.render(&tool.download_url, &version, &os, &arch);
+        let tmp_file = tempfile::tempfile()?;
+        let mut downloader = Downloader::new(&dot_dir, options)?;
+        downloader.download_file(&download_url, &tmp_file)?;
+        match &tool.checksum_url {
+            Some(checksum_url) => {
+                let shasums_download_url = tool.render(checksum_url, &version, &os, &arch);
+                let shasums = downloader.get_text(&shasums_download_url)?;
+                let file_name = download_url.rsplit('/').next().unwrap_or_default();
+                let sha256_regex = Regex::new(
+                    format!(r"(?m)^([a-f0-9]{{64}})\s+\*?{}$", regex::escape(file_name)).as_str(),
+                )?;
+                let expected_sha256 = regex_capture_group(&sha256_regex, 1, &shasums)?;
+                check_sha256_sum(&tmp_file, &expected_sha256)?;
+            }
+            None => {
+                wprintln(&format!(
+                    "Skipping SHA256 file integrity check, no checksum_url in {} tool descriptor",
+                    tool.name
+                ));
+            }
+        }
+        let binary_path = match &tool.binary_path {
+            Some(p) => tool.render(p, &version, &os, &arch),
+            None => tool.file_name.clone(),
+        };
+        extract_binary(
+            tmp_file.try_clone()?,
+            tool.archive,
+            &binary_path,
+            &opt_file_path,
+        )?;
+        write_install_manifest(
+            &dot_dir,
+            &opt_file_path,
+            &tmp_file,
+            Signature::Unsigned,
+            None,
+        )?;
+        downloader.commit()?;
+    }
+    Ok(format!("Installed {} {}", tool.name, version))
+}
+
+pub const TOOLS_DIR_NAME: &str = "tools.d";
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::write;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_load_tool_descriptors() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let tools_dir = dot_dir.etc.join(TOOLS_DIR_NAME);
+        write(
+            tools_dir.join("tflint.toml"),
+            r#"
+aliases = ["tfl"]
+git_repository_url = "https://github.com/terraform-linters/tflint"
+download_url = "https://github.com/terraform-linters/tflint/releases/download/v{version}/tflint_{os}_{arch}.zip"
+checksum_url = "https://github.com/terraform-linters/tflint/releases/download/v{version}/checksums.txt"
+
+[arch_names]
+amd64 = "x86_64"
+"#,
+        )
+        .unwrap();
+        let tool = find_tool_descriptor("tfl", &dot_dir).unwrap().unwrap();
+        assert_eq!(tool.name, "tflint");
+        assert_eq!(tool.file_name, format!("tflint{}", EXE_SUFFIX));
+        assert_eq!(tool.archive, None);
+        assert_eq!(
+            tool.render(&tool.download_url, "0.40.0", "linux", "amd64"),
+            "https://github.com/terraform-linters/tflint/releases/download/v0.40.0/tflint_linux_x86_64.zip"
+        );
+        assert!(find_tool_descriptor("tfsec", &dot_dir).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_load_tool_descriptor_reserved_name() {
+        let home = tempdir().unwrap();
+        let dot_dir = DotDir::bootstrap(home.path()).unwrap();
+        let tools_dir = dot_dir.etc.join(TOOLS_DIR_NAME);
+        write(
+            tools_dir.join("tf.toml"),
+            "git_repository_url = \"x\"\ndownload_url = \"y\"\n",
+        )
+        .unwrap();
+        assert!(load_tool_descriptors(&dot_dir).is_err());
+    }
+}
@@ -1,20 +1,24 @@
-use git2::{Direction, Remote};
+#[cfg(feature = "git2")]
+use crate::config::Credentials;
+use crate::config::HttpConfig;
+#[cfg(feature = "git2")]
+use git2::{Cred, Direction, ProxyOptions, Remote, RemoteCallbacks};
 use pgp::{types::KeyTrait, SignedPublicKey, StandaloneSignature};
 use regex::Regex;
 use semver::Version;
 use sha2::{Digest, Sha256};
 use std::{
     error::Error,
-    fs::File,
-    io::{copy, stderr, stdout, Seek, SeekFrom, Write},
+    fs::{hard_link, read_dir, remove_file, rename, File},
+    io::{copy, stderr, stdout, Read, Seek, SeekFrom, Write},
+    path::Path,
+    process,
+    time::{Duration, SystemTime},
 };
+use tempfile::{NamedTempFile, TempPath};
 
-pub fn check_sha256_sum(mut file: &File, expected_sha256: &str) -> Result<(), Box<dyn Error>> {
-    file.seek(SeekFrom::Start(0))?;
-    let mut sha256 = Sha256::new();
-    copy(&mut file, &mut sha256)?;
-    let result = sha256.finalize();
-    let actual_sha256 = hex::encode(result);
+pub fn check_sha256_sum(file: &File, expected_sha256: &str) -> Result<(), Box<dyn Error>> {
+    let actual_sha256 = sha256_hex(file)?;
     if actual_sha256 != expected_sha256 {
         return Err(format!(
             "File sha256 checksum mismatch: expected '{}', got '{}'",
@@ -25,6 +29,14 @@ pub fn check_sha256_sum(mut file: &File, expected_sha256: &str) -> Result<(), Bo
     Ok(())
 }
 
+pub fn sha256_hex(mut file: &File) -> Result<String, Box<dyn Error>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut sha256 = Sha256::new();
+    copy(&mut file, &mut sha256)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(hex::encode(sha256.finalize()))
+}
+
 pub fn regex_capture_group(
     regex: &Regex,
     group: usize,
@@ -40,6 +52,20 @@ pub fn regex_capture_group(
     Ok(result)
 }
 
+// Checksum of given file from a SHA256SUMS file, whose lines are
+// "<sha256>  <file name>" (or "<sha256> *<file name>" in binary mode)
+pub fn get_expected_sha256(shasums: &str, file_name: &str) -> Result<String, Box<dyn Error>> {
+    let sha256_regex = Regex::new(
+        format!(
+            r"(?m)^([a-f0-9]{{64}})\s+\*?{}\s*$",
+            regex::escape(file_name)
+        )
+        .as_str(),
+    )?;
+    regex_capture_group(&sha256_regex, 1, shasums)
+        .map_err(|_| format!("No SHA256 checksum found for {}", file_name).into())
+}
+
 pub fn to_sorted_multiline_string(versions: &mut Vec<Version>) -> String {
     versions.sort();
     versions.dedup();
@@ -69,20 +95,171 @@ pub fn verify_detached_pgp_signature(
     Err("PGP signature verification failed".into())
 }
 
-pub fn git_list_remote_tags(repo_url: &str) -> Result<Vec<String>, Box<dyn Error>> {
+// Writes content into a temporary file in the same directory as given path,
+// syncs it to disk and renames it into place, so that an interrupted or failed
+// write never leaves a partially written file at given path
+pub fn write_file_atomically(
+    path: &Path,
+    content: &mut dyn Read,
+    executable: bool,
+) -> Result<(), Box<dyn Error>> {
+    let tmp_path = write_tmp_file_for(path, content, executable)?;
+    persist_tmp_path(tmp_path, path)
+}
+
+// Writes content into a temporary file in the same directory as given path,
+// and closes it (e.g. so that it can be run) without renaming it into place.
+// The file is removed when dropped, unless persisted with persist_tmp_path.
+pub fn write_tmp_file_for(
+    path: &Path,
+    content: &mut dyn Read,
+    executable: bool,
+) -> Result<TempPath, Box<dyn Error>> {
+    let mut tmp_file = create_tmp_file_for(path)?;
+    copy(content, &mut tmp_file)?;
+    #[cfg(unix)]
+    if executable {
+        use std::fs::{set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+        set_permissions(tmp_file.path(), Permissions::from_mode(0o755))?;
+    }
+    #[cfg(windows)]
+    let _ = executable;
+    tmp_file.as_file().sync_all()?;
+    Ok(tmp_file.into_temp_path())
+}
+
+// Temporary file in the same directory as given path, so that it can be
+// atomically renamed into place with persist_tmp_file
+pub fn create_tmp_file_for(path: &Path) -> Result<NamedTempFile, Box<dyn Error>> {
+    let dir = path.parent().ok_or("Invalid file path")?;
+    let tmp_file = tempfile::Builder::new()
+        .prefix(TMP_FILE_PREFIX)
+        .tempfile_in(dir)?;
+    #[cfg(unix)]
+    {
+        use std::fs::{set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+        set_permissions(tmp_file.path(), Permissions::from_mode(0o644))?;
+    }
+    Ok(tmp_file)
+}
+
+pub fn persist_tmp_file(tmp_file: NamedTempFile, path: &Path) -> Result<(), Box<dyn Error>> {
+    persist_tmp_path(tmp_file.into_temp_path(), path)
+}
+
+pub fn persist_tmp_path(tmp_path: TempPath, path: &Path) -> Result<(), Box<dyn Error>> {
+    tmp_path.persist(path)?;
+    #[cfg(unix)]
+    File::open(path.parent().ok_or("Invalid file path")?)?.sync_all()?;
+    Ok(())
+}
+
+// Creates a hard link under a temporary name and renames it over given link
+// path, so that there is no moment when the link path does not exist
+pub fn hard_link_atomically(original: &Path, link: &Path) -> Result<(), Box<dyn Error>> {
+    let dir = link.parent().ok_or("Invalid file path")?;
+    let file_name = link.file_name().ok_or("Invalid file path")?;
+    let tmp_link = dir.join(format!(
+        "{}{}-{}",
+        TMP_FILE_PREFIX,
+        process::id(),
+        file_name.to_string_lossy()
+    ));
+    if tmp_link.exists() {
+        remove_file(&tmp_link)?;
+    }
+    hard_link(original, &tmp_link)?;
+    let result = rename(&tmp_link, link);
+    // Renaming a link over another link to the same file is a no-op (POSIX),
+    // leaving the temporary link in place
+    if tmp_link.exists() {
+        remove_file(&tmp_link)?;
+    }
+    result?;
+    Ok(())
+}
+
+// Removes temporary files left behind by killed processes. Recent files are
+// kept, as they may belong to a concurrently running process.
+pub fn remove_stale_tmp_files(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let now = SystemTime::now();
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(TMP_FILE_PREFIX)
+        {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        if now.duration_since(modified).unwrap_or_default() > STALE_TMP_FILE_AGE {
+            remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+// Proxy and credentials are applied from http config. libgit2 has its own
+// OpenSSL setup, which reads CA certificates from SSL_CERT_FILE instead.
+#[cfg(feature = "git2")]
+pub fn git_list_remote_tags(
+    repo_url: &str,
+    http_config: &HttpConfig,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    if !http_config.ca_certificates.is_empty() || http_config.client_identity.is_some() {
+        wprintln("CA certificates and client identity are not applied to git, see https://github.com/superblk/terve#http");
+    }
+    let host = reqwest::Url::parse(repo_url)?
+        .host_str()
+        .unwrap_or_default()
+        .to_string();
+    let credentials = http_config.get_credentials(&host)?;
+    let mut attempted = false;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |_, username, _| {
+        // Called again if credentials are rejected, give up then
+        if attempted {
+            return Err(git2::Error::from_str("Authentication failed"));
+        }
+        attempted = true;
+        match &credentials {
+            Some(Credentials::Bearer(token)) => {
+                Cred::userpass_plaintext(username.unwrap_or("x-access-token"), token)
+            }
+            Some(Credentials::Basic(login, password)) => Cred::userpass_plaintext(login, password),
+            None => Err(git2::Error::from_str("No credentials configured")),
+        }
+    });
+    let mut proxy_options = ProxyOptions::new();
+    if let Some(proxy) = http_config.get_proxy() {
+        if !http_config.is_no_proxy(&host) {
+            proxy_options.url(&proxy);
+        }
+    }
     let mut remote = Remote::create_detached(repo_url)?;
-    remote.connect(Direction::Fetch)?;
-    let result = remote
+    let connection = remote.connect_auth(Direction::Fetch, Some(callbacks), Some(proxy_options))?;
+    let result = connection
         .list()?
         .iter()
         .map(|h| h.name().to_string())
         .filter(|r| r.starts_with("refs/tags/") && !r.ends_with("^{}"))
         .map(|s| s.trim_start_matches("refs/tags/").to_owned())
         .collect();
-    remote.disconnect()?;
     Ok(result)
 }
 
+// Without git2, tags are listed via the GitHub API
+#[cfg(not(feature = "git2"))]
+pub fn git_list_remote_tags(
+    repo_url: &str,
+    http_config: &HttpConfig,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    crate::github::list_tags(repo_url, http_config)
+}
+
 // We do not use vanilla println macros because:
 // https://github.com/rust-lang/rust/issues/46016
 // https://stackoverflow.com/a/37558917
@@ -105,11 +282,15 @@ pub fn wprintln(msg: &str) {
     let _ = write!(&mut lock, "WARNING: {}{}", msg, NEWLINE);
 }
 
+const TMP_FILE_PREFIX: &str = ".terve-tmp-";
+
+const STALE_TMP_FILE_AGE: Duration = Duration::from_secs(60 * 60);
+
 #[cfg(unix)]
-const NEWLINE: &str = "\n";
+pub const NEWLINE: &str = "\n";
 
 #[cfg(windows)]
-const NEWLINE: &str = "\r\n";
+pub const NEWLINE: &str = "\r\n";
 
 #[cfg(test)]
 mod tests {
@@ -152,6 +333,27 @@ mod tests {
         assert!(regex_capture_group(&regex, 1, &str_no_match).is_err());
     }
 
+    #[test]
+    fn test_get_expected_sha256() {
+        let shasums = "\
+1111111111111111111111111111111111111111111111111111111111111111  terragrunt_linux_amd64.tar.gz
+2222222222222222222222222222222222222222222222222222222222222222  terragrunt_linux_amd64
+3333333333333333333333333333333333333333333333333333333333333333  tofu_1.6.0_linux_amd64.zip
+4444444444444444444444444444444444444444444444444444444444444444  tofu_1x6x0_linux_arm64.zip
+";
+        assert_eq!(
+            get_expected_sha256(shasums, "terragrunt_linux_amd64").unwrap(),
+            "2222222222222222222222222222222222222222222222222222222222222222"
+        );
+        assert_eq!(
+            get_expected_sha256(shasums, "tofu_1.6.0_linux_amd64.zip").unwrap(),
+            "3333333333333333333333333333333333333333333333333333333333333333"
+        );
+        // Dots match literally
+        assert!(get_expected_sha256(shasums, "tofu_1.6.0_linux_arm64.zip").is_err());
+        assert!(get_expected_sha256(shasums, "terragrunt_darwin_amd64").is_err());
+    }
+
     #[test]
     fn test_version_sort() {
         let mut versions = vec!["0.13.4", "0.15.4", "0.1.0", "0.13.4"]
@@ -194,9 +396,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_write_file_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("1.2.3");
+        write_file_atomically(&path, &mut "old".as_bytes(), true).unwrap();
+        write_file_atomically(&path, &mut "new".as_bytes(), true).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "new");
+        let stale_tmp_file_path = dir.path().join(format!("{}abc", TMP_FILE_PREFIX));
+        File::create(&stale_tmp_file_path)
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_TMP_FILE_AGE * 2)
+            .unwrap();
+        let fresh_tmp_file_path = dir.path().join(format!("{}def", TMP_FILE_PREFIX));
+        File::create(&fresh_tmp_file_path).unwrap();
+        remove_stale_tmp_files(dir.path()).unwrap();
+        assert!(!stale_tmp_file_path.exists());
+        assert!(fresh_tmp_file_path.exists());
+        assert_eq!(read_dir(dir.path()).unwrap().count(), 2);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = path.metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    #[test]
+    fn test_hard_link_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_path = dir.path().join("old");
+        let new_path = dir.path().join("new");
+        let link_path = dir.path().join("link");
+        std::fs::write(&old_path, "old").unwrap();
+        std::fs::write(&new_path, "new").unwrap();
+        hard_link_atomically(&old_path, &link_path).unwrap();
+        assert_eq!(read_to_string(&link_path).unwrap(), "old");
+        hard_link_atomically(&new_path, &link_path).unwrap();
+        hard_link_atomically(&new_path, &link_path).unwrap();
+        assert_eq!(read_to_string(&link_path).unwrap(), "new");
+        assert_eq!(read_to_string(&old_path).unwrap(), "old");
+        assert_eq!(read_dir(dir.path()).unwrap().count(), 3);
+    }
+
     #[test]
     fn test_git_list_remote_tags() {
-        let tags = git_list_remote_tags("https://github.com/gruntwork-io/terragrunt").unwrap();
+        let tags = git_list_remote_tags(
+            "https://github.com/gruntwork-io/terragrunt",
+            &HttpConfig::default(),
+        )
+        .unwrap();
         assert!(tags.contains(&"v0.29.7".to_string()));
     }
 }
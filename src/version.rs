@@ -0,0 +1,298 @@
+use std::{
+    env::{current_dir, var},
+    error::Error,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use regex::Regex;
+use semver::{Prerelease, Version};
+
+use crate::{
+    constraint::VersionConstraint,
+    hcl::{read_terraform_required_versions, read_terragrunt_attribute},
+    shared::Binary,
+};
+
+pub enum VersionSpec {
+    Latest,
+    LatestMatching(Regex),
+    MinRequired,
+    LatestAllowed,
+    Constraint(VersionConstraint),
+}
+
+impl FromStr for VersionSpec {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latest" => Ok(VersionSpec::Latest),
+            "min-required" => Ok(VersionSpec::MinRequired),
+            "latest-allowed" => Ok(VersionSpec::LatestAllowed),
+            _ => match s.strip_prefix("latest:") {
+                Some(r) => Ok(VersionSpec::LatestMatching(Regex::new(r)?)),
+                None => Ok(VersionSpec::Constraint(VersionConstraint::from_str(s)?)),
+            },
+        }
+    }
+}
+
+pub fn resolve_version(
+    binary: &Binary,
+    spec: &str,
+    versions: &[Version],
+) -> Result<String, Box<dyn Error>> {
+    let version = match VersionSpec::from_str(spec)? {
+        VersionSpec::Latest => versions.iter().filter(|v| v.pre == Prerelease::EMPTY).max(),
+        VersionSpec::LatestMatching(regex) => versions
+            .iter()
+            .filter(|v| regex.is_match(&v.to_string()))
+            .max(),
+        VersionSpec::MinRequired => {
+            let constraint = get_required_version(binary, &current_dir()?)?;
+            constraint.resolve_min(versions)
+        }
+        VersionSpec::LatestAllowed => {
+            let constraint = get_required_version(binary, &current_dir()?)?;
+            constraint.resolve(versions)
+        }
+        VersionSpec::Constraint(constraint) => constraint.resolve(versions),
+    }
+    .ok_or(format!("No {} version matches '{}'", binary, spec))?;
+    Ok(version.to_string())
+}
+
+// Wanted version is read from environment variable TERVE_<BINARY>_VERSION, or
+// from the nearest .<binary>-version or .tool-versions file, walking up from
+// given directory. Returns the version and a description of where it came from.
+pub fn get_wanted_version(
+    binary: &Binary,
+    dir: &Path,
+) -> Result<Option<(String, String)>, Box<dyn Error>> {
+    let env_var_name = get_version_env_var_name(binary);
+    find_wanted_version(binary, dir, var(&env_var_name).ok(), None)
+}
+
+fn get_version_env_var_name(binary: &Binary) -> String {
+    format!("TERVE_{}_VERSION", binary)
+        .to_uppercase()
+        .replace('-', "_")
+}
+
+// Version files are looked up from given dir up to top dir (if given), or the
+// filesystem root
+fn find_wanted_version(
+    binary: &Binary,
+    dir: &Path,
+    env_var_value: Option<String>,
+    top_dir: Option<&Path>,
+) -> Result<Option<(String, String)>, Box<dyn Error>> {
+    if let Some(version) = env_var_value {
+        if !version.trim().is_empty() {
+            let source = format!("{} environment variable", get_version_env_var_name(binary));
+            return Ok(Some((version.trim().to_string(), source)));
+        }
+    }
+    let version_file_name = format!(".{}-version", binary);
+    let mut dir: Option<&Path> = Some(dir);
+    while let Some(d) = dir {
+        let version_file_path = d.join(&version_file_name);
+        if version_file_path.is_file() {
+            if let Some(version) = read_version_file(&version_file_path)? {
+                return Ok(Some((version, version_file_path.display().to_string())));
+            }
+        }
+        let tool_versions_path = d.join(".tool-versions");
+        if tool_versions_path.is_file() {
+            if let Some(version) = read_tool_versions_file(binary, &tool_versions_path)? {
+                return Ok(Some((version, tool_versions_path.display().to_string())));
+            }
+        }
+        if Some(d) == top_dir {
+            break;
+        }
+        dir = d.parent();
+    }
+    Ok(None)
+}
+
+pub fn resolve_wanted_version(binary: &Binary) -> Result<(String, String), Box<dyn Error>> {
+    let cwd: PathBuf = current_dir()?;
+    get_wanted_version(binary, &cwd)?.ok_or_else(|| {
+        format!(
+            "No {0} version given, and none found in .{0}-version or .tool-versions files",
+            binary
+        )
+        .into()
+    })
+}
+
+fn read_version_file(path: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    let version = read_to_string(path)?
+        .lines()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.to_string());
+    Ok(version)
+}
+
+// See https://asdf-vm.com/manage/configuration.html#tool-versions
+fn read_tool_versions_file(binary: &Binary, path: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    let tool_name = binary.to_string();
+    let version = read_to_string(path)?
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or_default())
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some(name), Some(version)) if name == tool_name => Some(version.to_string()),
+                _ => None,
+            }
+        })
+        .next();
+    Ok(version)
+}
+
+// Collects version constraints for given binary from configuration files in
+// given directory: required_version in *.tf files (terraform, opentofu) and
+// terraform_version_constraint or terragrunt_version_constraint in terragrunt.hcl
+fn get_required_version(binary: &Binary, dir: &Path) -> Result<VersionConstraint, Box<dyn Error>> {
+    let constraints = match binary {
+        Binary::Terraform | Binary::OpenTofu => {
+            let mut constraints = read_terraform_required_versions(dir)?;
+            constraints.extend(read_terragrunt_attribute(
+                dir,
+                "terraform_version_constraint",
+            )?);
+            constraints
+        }
+        Binary::Terragrunt => read_terragrunt_attribute(dir, "terragrunt_version_constraint")?
+            .into_iter()
+            .collect(),
+        Binary::HashiCorp(_) | Binary::Tool(_) => Vec::new(),
+    };
+    if constraints.is_empty() {
+        return Err(format!(
+            "No {} version constraints found in {}",
+            binary,
+            dir.display()
+        )
+        .into());
+    }
+    VersionConstraint::from_str(&constraints.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::{create_dir_all, write};
+
+    use tempfile::tempdir;
+
+    use crate::hashicorp::TERRAFORM_LS;
+
+    use super::*;
+
+    #[test]
+    fn test_get_required_version() {
+        let dir = tempdir().unwrap();
+        write(
+            dir.path().join("versions.tf"),
+            "terraform {\n  required_version = \">= 1.3\"\n}\n",
+        )
+        .unwrap();
+        write(
+            dir.path().join("main.tf"),
+            "terraform {\n  required_version = \"< 1.6\"\n}\n",
+        )
+        .unwrap();
+        write(dir.path().join("README.md"), "required_version = \"1.0\"").unwrap();
+        write(
+            dir.path().join("terragrunt.hcl"),
+            "terraform_version_constraint = \"!= 1.5.6\"\nterragrunt_version_constraint = \">= 0.45\"\n",
+        )
+        .unwrap();
+        let constraint = get_required_version(&Binary::Terraform, dir.path()).unwrap();
+        assert!(constraint.matches(&Version::parse("1.5.7").unwrap()));
+        assert!(!constraint.matches(&Version::parse("1.5.6").unwrap()));
+        assert!(!constraint.matches(&Version::parse("1.6.0").unwrap()));
+        assert!(!constraint.matches(&Version::parse("1.0.0").unwrap()));
+        let constraint = get_required_version(&Binary::Terragrunt, dir.path()).unwrap();
+        assert!(constraint.matches(&Version::parse("0.53.0").unwrap()));
+        assert!(!constraint.matches(&Version::parse("0.44.0").unwrap()));
+    }
+
+    #[test]
+    fn test_get_wanted_version() {
+        let root = tempdir().unwrap();
+        let sub_dir = root.path().join("live").join("prod");
+        create_dir_all(&sub_dir).unwrap();
+        let top_dir = Some(root.path());
+        assert!(
+            find_wanted_version(&Binary::Terragrunt, &sub_dir, None, top_dir)
+                .unwrap()
+                .is_none()
+        );
+        write(
+            root.path().join(".tool-versions"),
+            "# tools\nterraform 1.4.6\nterragrunt 0.45.0 # pinned\n",
+        )
+        .unwrap();
+        write(
+            root.path().join("live").join(".terraform-version"),
+            "1.5.7\n",
+        )
+        .unwrap();
+        let (version, source) = find_wanted_version(&Binary::Terraform, &sub_dir, None, top_dir)
+            .unwrap()
+            .unwrap();
+        assert_eq!(version, "1.5.7");
+        assert!(source.ends_with(".terraform-version"));
+        let (version, source) = find_wanted_version(&Binary::Terragrunt, &sub_dir, None, top_dir)
+            .unwrap()
+            .unwrap();
+        assert_eq!(version, "0.45.0");
+        assert!(source.ends_with(".tool-versions"));
+        // Environment variable takes precedence over version files, unless empty
+        let env_var_value = Some(" 0.53.0\n".to_string());
+        let (version, source) =
+            find_wanted_version(&Binary::Terragrunt, &sub_dir, env_var_value, top_dir)
+                .unwrap()
+                .unwrap();
+        assert_eq!(version, "0.53.0");
+        assert_eq!(source, "TERVE_TERRAGRUNT_VERSION environment variable");
+        let (version, _) = find_wanted_version(
+            &Binary::Terragrunt,
+            &sub_dir,
+            Some(" ".to_string()),
+            top_dir,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(version, "0.45.0");
+        assert_eq!(
+            get_version_env_var_name(&Binary::HashiCorp(&TERRAFORM_LS)),
+            "TERVE_TERRAFORM_LS_VERSION"
+        );
+    }
+
+    #[test]
+    fn test_resolve_keywords() {
+        let versions: Vec<Version> = ["0.38.4", "0.40.2", "0.45.0", "0.50.0-alpha1"]
+            .iter()
+            .map(|s| Version::parse(s).unwrap())
+            .collect();
+        let binary = Binary::Terragrunt;
+        assert_eq!(
+            resolve_version(&binary, "latest", &versions).unwrap(),
+            "0.45.0"
+        );
+        assert_eq!(
+            resolve_version(&binary, r"latest:^0\.4[0-4]\.", &versions).unwrap(),
+            "0.40.2"
+        );
+        assert!(resolve_version(&binary, "latest:^1\\.", &versions).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::{error::Error, path::Path, process::Command};
+
+use regex::Regex;
+use semver::Version;
+use serde::Deserialize;
+
+use crate::shared::{get_os_arch, Binary};
+
+// Output of `terraform version -json` (also `tofu version -json`)
+#[derive(Deserialize)]
+struct VersionOutput {
+    terraform_version: String,
+    platform: Option<String>,
+}
+
+// Runs an installed binary to check that it reports the expected version and
+// platform, e.g. that a mirror did not serve another (validly signed) build.
+// Only terraform, opentofu and terragrunt are checked.
+pub fn check_installed_version(
+    binary: &Binary,
+    opt_file_path: &Path,
+    version: &str,
+    os: &str,
+    arch: &str,
+) -> Result<(), Box<dyn Error>> {
+    let (actual_version, actual_platform) = match binary {
+        Binary::Terraform | Binary::OpenTofu => {
+            let output = run_binary(opt_file_path, &["version", "-json"])?;
+            parse_terraform_version_output(&output)?
+        }
+        Binary::Terragrunt => {
+            let output = run_binary(opt_file_path, &["--version"])?;
+            (parse_terragrunt_version_output(&output)?, None)
+        }
+        _ => return Ok(()),
+    };
+    if Version::parse(&actual_version)? != Version::parse(version)? {
+        return Err(format!(
+            "Installed {} reports version {}, expected {}",
+            binary, actual_version, version
+        )
+        .into());
+    }
+    let expected_platform = format!("{}_{}", os, arch);
+    if let Some(actual_platform) = actual_platform {
+        if actual_platform != expected_platform {
+            return Err(format!(
+                "Installed {} {} reports platform {}, expected {}",
+                binary, version, actual_platform, expected_platform
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+// Checks a newly extracted binary (still in a temporary file) before it is
+// put into place and its downloads are committed to the cache, so that a bad
+// artifact is neither installed nor cached. Binaries for other platforms (e.g.
+// bundle exports) cannot be run, and are not checked.
+pub fn check_new_binary_version(
+    binary: &Binary,
+    file_path: &Path,
+    version: &str,
+    os: &str,
+    arch: &str,
+) -> Result<(), Box<dyn Error>> {
+    if (os.to_string(), arch.to_string()) != get_os_arch() {
+        return Ok(());
+    }
+    check_installed_version(binary, file_path, version, os, arch)
+        .map_err(|e| format!("{}, not installing it", e).into())
+}
+
+fn run_binary(opt_file_path: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new(opt_file_path)
+        .args(args)
+        .env("CHECKPOINT_DISABLE", "1")
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", opt_file_path.display(), e))?;
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Terraform versions before 0.13 do not support -json, and print e.g.
+// "Terraform v0.12.31" (newer ones add "on linux_amd64")
+fn parse_terraform_version_output(
+    output: &str,
+) -> Result<(String, Option<String>), Box<dyn Error>> {
+    if let Ok(version_output) = serde_json::from_str::<VersionOutput>(output) {
+        return Ok((version_output.terraform_version, version_output.platform));
+    }
+    let version_regex = Regex::new(r"(?m)^\w+ v(\S+)$")?;
+    let platform_regex = Regex::new(r"(?m)^on (\w+)$")?;
+    let version = version_regex
+        .captures(output)
+        .map(|c| c[1].to_string())
+        .ok_or_else(|| format!("Unable to parse version output: {}", output.trim()))?;
+    let platform = platform_regex.captures(output).map(|c| c[1].to_string());
+    Ok((version, platform))
+}
+
+// E.g. "terragrunt version v0.38.4" (or without the v, in newer versions)
+fn parse_terragrunt_version_output(output: &str) -> Result<String, Box<dyn Error>> {
+    let version_regex = Regex::new(r"version v?(\S+)")?;
+    let version = version_regex
+        .captures(output)
+        .map(|c| c[1].to_string())
+        .ok_or_else(|| format!("Unable to parse version output: {}", output.trim()))?;
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_terraform_version_output() {
+        let output = r#"{
+  "terraform_version": "1.5.7",
+  "platform": "linux_amd64",
+  "provider_selections": {},
+  "terraform_outdated": false
+}"#;
+        assert_eq!(
+            parse_terraform_version_output(output).unwrap(),
+            ("1.5.7".to_string(), Some("linux_amd64".to_string()))
+        );
+        assert_eq!(
+            parse_terraform_version_output("Terraform v0.12.31\n").unwrap(),
+            ("0.12.31".to_string(), None)
+        );
+        assert_eq!(
+            parse_terraform_version_output("Terraform v0.12.31\non darwin_amd64\n").unwrap(),
+            ("0.12.31".to_string(), Some("darwin_amd64".to_string()))
+        );
+        assert!(parse_terraform_version_output("garbage").is_err());
+    }
+
+    #[test]
+    fn test_parse_terragrunt_version_output() {
+        assert_eq!(
+            parse_terragrunt_version_output("terragrunt version v0.38.4\n").unwrap(),
+            "0.38.4"
+        );
+        assert_eq!(
+            parse_terragrunt_version_output("terragrunt version 0.67.0\n").unwrap(),
+            "0.67.0"
+        );
+        assert!(parse_terragrunt_version_output("").is_err());
+    }
+}